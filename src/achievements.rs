@@ -0,0 +1,214 @@
+use crate::user_data::Activity;
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Missed days before checking a day again counts as a comeback
+const COMEBACK_GAP: i64 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Badge {
+    FirstWeek,
+    FirstMonth,
+    FullYear,
+    Comeback,
+    PerfectMonth,
+}
+
+impl Badge {
+    pub const ALL: [Self; 5] = [
+        Self::FirstWeek,
+        Self::FirstMonth,
+        Self::FullYear,
+        Self::Comeback,
+        Self::PerfectMonth,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FirstWeek => "First week",
+            Self::FirstMonth => "First month",
+            Self::FullYear => "365 days",
+            Self::Comeback => "Comeback",
+            Self::PerfectMonth => "Perfect month",
+        }
+    }
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::FirstWeek => "Keep up an activity for 7 days in a row",
+            Self::FirstMonth => "Keep up an activity for 30 days in a row",
+            Self::FullYear => "Keep up an activity for 365 days in a row",
+            Self::Comeback => "Get back to an activity after missing a week",
+            Self::PerfectMonth => "Check every day of a calendar month",
+        }
+    }
+    /// The first date `activity` satisfied the rule of this badge
+    fn earned_at(self, activity: &Activity) -> Option<NaiveDate> {
+        match self {
+            Self::FirstWeek => streak_reached(activity, 7),
+            Self::FirstMonth => streak_reached(activity, 30),
+            Self::FullYear => streak_reached(activity, 365),
+            Self::Comeback => comeback(activity),
+            Self::PerfectMonth => perfect_month(activity),
+        }
+    }
+}
+
+/// Badges earned so far, with the date they were earned on.
+///
+/// Badges are kept even if the days that earned them are unchecked later.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Achievements {
+    pub earned: BTreeMap<Badge, NaiveDate>,
+}
+
+impl Achievements {
+    /// Record the badges newly earned by `activities`, and return them
+    pub fn update(&mut self, activities: &[Activity]) -> Vec<Badge> {
+        let mut new = Vec::new();
+        for badge in Badge::ALL {
+            if self.earned.contains_key(&badge) {
+                continue;
+            }
+            // The rules are about days done, which negative habits don't have
+            if let Some(date) = activities
+                .iter()
+                .filter(|a| !a.is_negative())
+                .filter_map(|a| badge.earned_at(a))
+                .min()
+            {
+                self.earned.insert(badge, date);
+                new.push(badge);
+            }
+        }
+        new
+    }
+}
+
+/// The date a streak of `len` days was first completed
+fn streak_reached(activity: &Activity, len: u32) -> Option<NaiveDate> {
+    let mut streak = 0;
+    let mut prev: Option<NaiveDate> = None;
+    for &date in &activity.dates {
+        if prev.and_then(|prev| prev.succ_opt()) == Some(date) {
+            streak += 1;
+        } else {
+            streak = 1;
+        }
+        if streak == len {
+            return Some(date);
+        }
+        prev = Some(date);
+    }
+    None
+}
+
+fn comeback(activity: &Activity) -> Option<NaiveDate> {
+    let mut prev: Option<NaiveDate> = None;
+    for &date in &activity.dates {
+        if matches!(prev, Some(prev) if (date - prev).num_days() > COMEBACK_GAP) {
+            return Some(date);
+        }
+        prev = Some(date);
+    }
+    None
+}
+
+/// The last day of the first month that has every day checked
+fn perfect_month(activity: &Activity) -> Option<NaiveDate> {
+    let mut run_start: Option<NaiveDate> = None;
+    let mut prev: Option<NaiveDate> = None;
+    for &date in &activity.dates {
+        if prev.and_then(|prev| prev.succ_opt()) != Some(date) {
+            run_start = Some(date);
+        }
+        prev = Some(date);
+        let is_month_end = !matches!(date.succ_opt(), Some(next) if next.month() == date.month());
+        let month_start = date.with_day(1).unwrap();
+        if is_month_end && matches!(run_start, Some(start) if start <= month_start) {
+            return Some(date);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+fn activity_with(dates: impl IntoIterator<Item = NaiveDate>) -> Activity {
+    let mut activity = Activity::new_default(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+    activity.dates.extend(dates);
+    activity
+}
+
+#[cfg(test)]
+fn days_from(start: NaiveDate, n: usize) -> impl Iterator<Item = NaiveDate> {
+    start.iter_days().take(n)
+}
+
+#[test]
+fn test_streak_badges() {
+    let start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let activity = activity_with(days_from(start, 6));
+    assert_eq!(Badge::FirstWeek.earned_at(&activity), None);
+    // A broken streak doesn't count
+    let eighth = NaiveDate::from_ymd_opt(2023, 1, 8).unwrap();
+    let activity = activity_with(days_from(start, 6).chain(days_from(eighth, 7)));
+    assert_eq!(
+        Badge::FirstWeek.earned_at(&activity),
+        NaiveDate::from_ymd_opt(2023, 1, 14)
+    );
+    let activity = activity_with(days_from(start, 365));
+    assert_eq!(
+        Badge::FirstMonth.earned_at(&activity),
+        NaiveDate::from_ymd_opt(2023, 1, 30)
+    );
+    assert_eq!(
+        Badge::FullYear.earned_at(&activity),
+        NaiveDate::from_ymd_opt(2023, 12, 31)
+    );
+}
+
+#[test]
+fn test_comeback_badge() {
+    let date = |day| NaiveDate::from_ymd_opt(2023, 1, day).unwrap();
+    // 6 missed days isn't a break yet
+    assert_eq!(
+        Badge::Comeback.earned_at(&activity_with([date(1), date(8)])),
+        None
+    );
+    assert_eq!(
+        Badge::Comeback.earned_at(&activity_with([date(1), date(8), date(16)])),
+        Some(date(16))
+    );
+    assert_eq!(Badge::Comeback.earned_at(&activity_with([date(20)])), None);
+}
+
+#[test]
+fn test_perfect_month_badge() {
+    let feb = NaiveDate::from_ymd_opt(2023, 2, 1).unwrap();
+    assert_eq!(
+        Badge::PerfectMonth.earned_at(&activity_with(days_from(feb, 27))),
+        None
+    );
+    // Starting in the middle of January, so only February is complete
+    let activity = activity_with(days_from(NaiveDate::from_ymd_opt(2023, 1, 10).unwrap(), 60));
+    assert_eq!(
+        Badge::PerfectMonth.earned_at(&activity),
+        NaiveDate::from_ymd_opt(2023, 2, 28)
+    );
+}
+
+#[test]
+fn test_update_achievements() {
+    let start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let mut achievements = Achievements::default();
+    let mut activities = vec![activity_with(days_from(start, 3)), activity_with([])];
+    assert_eq!(achievements.update(&activities), []);
+    activities[1].dates.extend(days_from(start, 7));
+    assert_eq!(achievements.update(&activities), [Badge::FirstWeek]);
+    // Earned badges stay, even if the days are unchecked
+    activities[1].dates.clear();
+    assert_eq!(achievements.update(&activities), []);
+    assert_eq!(
+        achievements.earned.get(&Badge::FirstWeek),
+        NaiveDate::from_ymd_opt(2023, 1, 7).as_ref()
+    );
+}
@@ -0,0 +1,138 @@
+use crate::{config::Config, Paths};
+use calen_do::{
+    date_util, server,
+    sync::{self, InstanceLock, SaveFile},
+    user_data::UserData,
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    net::TcpListener,
+};
+
+pub const USAGE: &str = "\
+Usage: calen-do [--test] [--data <FILE>] [COMMAND]
+
+Without a command, the calendar window is opened.
+
+Commands:
+  list             List the activities with their streaks
+  export <FILE>    Export all data as JSON, - for the standard output
+  import <FILE>    Replace all data with a JSON export, - for the standard input
+  merge <FILE>     Merge another save file, like the one of another computer
+  serve [ADDRESS]  Serve a JSON API for integrations, on 127.0.0.1:8417 by default
+  help             Show this message
+
+--test uses calen-do-test.dat in the current directory instead of the real data.
+--data uses another save file, like one in a synced folder, as does the save-file
+setting of config.toml.
+import and merge refuse to run while the calendar window is open, unless --force is given.";
+
+pub fn run(args: &[String], paths: &Paths, config: &Config) -> Result<(), Box<dyn Error>> {
+    let path = &paths.save_file;
+    let load = || UserData::load(path).map_err(|e| format!("Can't load {}: {e}", path.display()));
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let n_args = args.len();
+    args.retain(|&arg| arg != "--force");
+    let force = args.len() != n_args;
+    match args[..] {
+        ["list"] => list(load()?),
+        ["export", "-"] => load()?.export_json(io::stdout().lock()),
+        ["export", file] => load()?.export_json(BufWriter::new(File::create(file)?)),
+        ["import", file] => {
+            let data = if file == "-" {
+                UserData::import_json(io::stdin().lock())?
+            } else {
+                UserData::import_json(BufReader::new(File::open(file)?))?
+            };
+            let _instance = lock_instance(paths, force)?;
+            SaveFile::new(path.clone(), &paths.local_dir).overwrite(&data)?;
+            println!("Imported {} activities", data.activities.len());
+            Ok(())
+        }
+        ["merge", file] => {
+            let _instance = lock_instance(paths, force)?;
+            let mut save_file = SaveFile::new(path.clone(), &paths.local_dir);
+            let mut data = save_file
+                .load()
+                .map_err(|e| format!("Can't load {}: {e}", path.display()))?;
+            let other =
+                UserData::load(file.as_ref()).map_err(|e| format!("Can't load {file}: {e}"))?;
+            let report = data.merge(other);
+            let current_date = date_util::today();
+            for activity in &mut data.activities {
+                activity.update_freezes(current_date);
+            }
+            save_file.save(&mut data)?;
+            print!("{report}");
+            Ok(())
+        }
+        ["serve"] => serve(paths, server::DEFAULT_ADDRESS, config),
+        ["serve", address] => serve(paths, address, config),
+        ["help" | "--help" | "-h"] => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("Invalid command\n\n{USAGE}").into()),
+    }
+}
+
+/// Keeps the calendar window from opening writable while the save file is changed here.
+///
+/// Fails if it's already open, as it would write its own data back, unless `force` is set.
+fn lock_instance(paths: &Paths, force: bool) -> Result<Option<InstanceLock>, Box<dyn Error>> {
+    match InstanceLock::acquire(&sync::local_files(&paths.local_dir, &paths.save_file))? {
+        Some(lock) => Ok(Some(lock)),
+        None if force => Ok(None),
+        None => Err("Calen-Do is running with this save file. \
+                     Close it first, or use --force to change the file anyway."
+            .into()),
+    }
+}
+
+fn list(mut data: UserData) -> Result<(), Box<dyn Error>> {
+    let current_date = date_util::today();
+    for activity in &mut data.activities {
+        activity.update_freezes(current_date);
+        let name = if activity.group.is_empty() {
+            activity.name.clone()
+        } else {
+            format!("{} ({})", activity.name, activity.group)
+        };
+        let current = activity.current_streak(current_date);
+        let longest = activity.longest_streak(current_date);
+        if activity.is_negative() {
+            println!(
+                "{name}: {current} days clean, longest {longest}, {} relapses",
+                activity.dates.len()
+            );
+        } else {
+            println!(
+                "{name}: {current} day streak, longest {longest}, {} days done",
+                activity.dates.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn serve(paths: &Paths, address: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+    let path = &paths.save_file;
+    // Not next to the save file, which might be synced to other devices
+    let token_path = sync::local_files(&paths.local_dir, path).with_extension("token");
+    let token = server::load_or_create_token(&token_path)?;
+    let mut save_file = SaveFile::new(path.clone(), &paths.local_dir);
+    let data = save_file
+        .load()
+        .map_err(|e| format!("Can't load {}: {e}", path.display()))?;
+    let listener = TcpListener::bind(address)?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    println!(
+        "Send the token in {} as \"Authorization: Bearer <token>\"",
+        token_path.display()
+    );
+    // The same rules as in the calendar window
+    server::serve(listener, &token, config.edit_policy(), save_file, data);
+    Ok(())
+}
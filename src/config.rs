@@ -0,0 +1,245 @@
+#[cfg(feature = "gui")]
+use crate::ui::{KeyCombo, Language};
+#[cfg(feature = "gui")]
+use calen_do::date_util::WeekStart;
+use calen_do::edit_policy::EditPolicy;
+use serde::Deserialize;
+#[cfg(feature = "gui")]
+use sfml::window::Key;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// User configuration, loaded from `config.toml` in the config directory.
+///
+/// Every field is optional in the file, missing ones take their default value.
+/// Without the calendar window, its settings are ignored, so the same file works.
+#[derive(Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+#[cfg_attr(feature = "gui", serde(deny_unknown_fields))]
+pub struct Config {
+    /// Language of month and weekday names. Detected from the system locale if not set.
+    #[cfg(feature = "gui")]
+    pub language: Option<Language>,
+    /// First day of the week. Detected from the system locale if not set.
+    #[cfg(feature = "gui")]
+    pub week_start: Option<WeekStart>,
+    /// How many days before today can be checked outside of edit mode
+    pub edit_window_days: u32,
+    /// Whether edit mode allows checking days after today
+    pub allow_future_edits: bool,
+    /// Ask for confirmation before editing days older than the edit window
+    pub confirm_old_edits: bool,
+    /// Save file to use instead of the one in the data directory, like one in a folder
+    /// that is synced with other devices
+    pub save_file: Option<PathBuf>,
+    /// Seconds between automatic saves, 0 disables autosave
+    #[cfg(feature = "gui")]
+    pub autosave_interval_secs: u64,
+    #[cfg(feature = "gui")]
+    pub theme: Theme,
+    #[cfg(feature = "gui")]
+    pub keys: Keys,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let edit_policy = EditPolicy::default();
+        Self {
+            #[cfg(feature = "gui")]
+            language: None,
+            #[cfg(feature = "gui")]
+            week_start: None,
+            edit_window_days: edit_policy.grace_days,
+            allow_future_edits: edit_policy.allow_future,
+            confirm_old_edits: edit_policy.confirm_old,
+            save_file: None,
+            #[cfg(feature = "gui")]
+            autosave_interval_secs: 300,
+            #[cfg(feature = "gui")]
+            theme: Theme::default(),
+            #[cfg(feature = "gui")]
+            keys: Keys::default(),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Theme {
+    /// Outlines of the current month and today
+    pub accent: Rgb,
+    /// Day numbers of today and filled days in overview
+    pub accent_bright: Rgb,
+    /// Clear color, only visible with the background shader disabled
+    pub background: Rgb,
+    pub background_shader: bool,
+}
+
+#[cfg(feature = "gui")]
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Rgb(231, 183, 13),
+            accent_bright: Rgb(255, 222, 92),
+            background: Rgb(255, 255, 255),
+            background_shader: true,
+        }
+    }
+}
+
+/// A color, written like "#e7b70d" in the config file
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+#[cfg(feature = "gui")]
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        let err = || format!("Invalid color \"{string}\", expected a hex color like \"#e7b70d\"");
+        let hex = string.strip_prefix('#').ok_or_else(err)?;
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(err());
+        }
+        let component = |i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+        Ok(Self(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Keys {
+    pub prev_activity: KeyCombo,
+    pub next_activity: KeyCombo,
+    pub overview: KeyCombo,
+    pub edit_mode: KeyCombo,
+    pub rename_activity: KeyCombo,
+    /// Browse the next group of activities
+    pub next_group: KeyCombo,
+    /// First activity of the group being browsed
+    pub first_activity: KeyCombo,
+    /// Last activity of the group being browsed
+    pub last_activity: KeyCombo,
+    /// Type a part of an activity name to jump to it
+    pub search_activity: KeyCombo,
+    /// Check or uncheck today for the current activity
+    pub toggle_today: KeyCombo,
+    pub quit: KeyCombo,
+}
+
+#[cfg(feature = "gui")]
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            prev_activity: KeyCombo::plain(Key::Left),
+            next_activity: KeyCombo::plain(Key::Right),
+            overview: KeyCombo::plain(Key::O),
+            edit_mode: KeyCombo::plain(Key::E),
+            rename_activity: KeyCombo::plain(Key::F2),
+            next_group: KeyCombo::plain(Key::G),
+            first_activity: KeyCombo::plain(Key::Home),
+            last_activity: KeyCombo::plain(Key::End),
+            search_activity: KeyCombo::ctrl(Key::F),
+            toggle_today: KeyCombo::plain(Key::Space),
+            quit: KeyCombo::ctrl(Key::Q),
+        }
+    }
+}
+
+impl Config {
+    pub fn edit_policy(&self) -> EditPolicy {
+        EditPolicy {
+            grace_days: self.edit_window_days,
+            allow_future: self.allow_future_edits,
+            confirm_old: self.confirm_old_edits,
+        }
+    }
+    /// Loads the config file at `path`, or the default config if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(format!("Error reading config file {}: {e}", path.display()).into())
+            }
+        };
+        Self::parse(&text)
+            .map_err(|e| format!("Error in config file {}: {e}", path.display()).into())
+    }
+    fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_parse_config() {
+    let config = Config::parse(
+        r##"
+        week-start = "sunday"
+        language = "hu"
+        edit-window-days = 3
+        save-file = "/home/me/Sync/calen-do.dat"
+
+        [theme]
+        accent = "#102030"
+
+        [keys]
+        overview = "Ctrl+O"
+        "##,
+    )
+    .unwrap();
+    assert_eq!(config.week_start, Some(WeekStart::Sunday));
+    assert_eq!(config.language, Some(Language::Hungarian));
+    assert_eq!(config.edit_window_days, 3);
+    assert_eq!(config.autosave_interval_secs, 300);
+    assert_eq!(
+        config.save_file.as_deref(),
+        Some(Path::new("/home/me/Sync/calen-do.dat"))
+    );
+    assert_eq!(config.theme.accent, Rgb(0x10, 0x20, 0x30));
+    assert!(config.theme.background_shader);
+    assert_eq!(config.keys.overview, KeyCombo::ctrl(Key::O));
+    assert_eq!(config.keys.quit, KeyCombo::ctrl(Key::Q));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn test_invalid_config() {
+    for text in [
+        "week-start = \"friday\"",
+        "language = \"xx\"",
+        "edit-window-days = -1",
+        "unknown-setting = 1",
+        "[theme]\naccent = \"#12345\"",
+        "[theme]\naccent = \"#12345g\"",
+        "[keys]\nquit = \"Ctrl+Nope\"",
+    ] {
+        assert!(Config::parse(text).is_err(), "{text}");
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+#[test]
+fn test_parse_config_without_window() {
+    let config = Config::parse(
+        r##"
+        edit-window-days = 3
+        language = "hu"
+
+        [keys]
+        overview = "Ctrl+O"
+        "##,
+    )
+    .unwrap();
+    assert_eq!(config.edit_window_days, 3);
+    assert!(!config.allow_future_edits);
+    assert_eq!(config.save_file, None);
+    assert!(Config::parse("edit-window-days = -1").is_err());
+}
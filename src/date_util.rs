@@ -1,4 +1,10 @@
-use chrono::NaiveDate;
+use chrono::{Local, NaiveDate, Weekday};
+use serde::Deserialize;
+
+/// The current date in the local time zone
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
 
 pub fn month_year_offset(month: i32, mut year: i32, offset: i32) -> (i32, i32) {
     let mut new_month = month + offset;
@@ -33,3 +39,58 @@ pub fn days_in_month(year: i32, month: u8) -> u8 {
 
 pub const DAYS_PER_WEEK: u8 = 7;
 pub const MONTHS_PER_YEAR: u8 = 12;
+
+/// The day of the week that calendar rows start with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    Sunday,
+    Monday,
+    Saturday,
+}
+
+impl WeekStart {
+    pub fn first_weekday(self) -> Weekday {
+        match self {
+            Self::Sunday => Weekday::Sun,
+            Self::Monday => Weekday::Mon,
+            Self::Saturday => Weekday::Sat,
+        }
+    }
+    /// Column of `weekday` in a week row
+    pub fn column(self, weekday: Weekday) -> u8 {
+        let first = self.first_weekday().num_days_from_monday();
+        ((weekday.num_days_from_monday() + u32::from(DAYS_PER_WEEK) - first)
+            % u32::from(DAYS_PER_WEEK)) as u8
+    }
+    /// Weekday displayed in `column` of a week row
+    pub fn weekday_at(self, column: u8) -> Weekday {
+        (0..column).fold(self.first_weekday(), |wd, _| wd.succ())
+    }
+    /// The customary week start for a region code, like "US" or "DE"
+    pub fn for_region(region: &str) -> Self {
+        match region {
+            "US" | "CA" | "MX" | "BR" | "JP" | "KR" | "TW" | "HK" | "PH" | "IL" | "IN" | "ZA"
+            | "SA" => Self::Sunday,
+            "EG" | "IR" | "AF" | "DZ" | "LY" | "SY" | "IQ" | "JO" | "KW" | "QA" | "OM" => {
+                Self::Saturday
+            }
+            _ => Self::Monday,
+        }
+    }
+}
+
+#[test]
+fn test_week_start() {
+    assert_eq!(WeekStart::Monday.column(Weekday::Mon), 0);
+    assert_eq!(WeekStart::Monday.column(Weekday::Sun), 6);
+    assert_eq!(WeekStart::Sunday.column(Weekday::Sun), 0);
+    assert_eq!(WeekStart::Sunday.column(Weekday::Mon), 1);
+    assert_eq!(WeekStart::Saturday.column(Weekday::Sat), 0);
+    assert_eq!(WeekStart::Saturday.column(Weekday::Fri), 6);
+    for ws in [WeekStart::Sunday, WeekStart::Monday, WeekStart::Saturday] {
+        for col in 0..DAYS_PER_WEEK {
+            assert_eq!(ws.column(ws.weekday_at(col)), col);
+        }
+    }
+}
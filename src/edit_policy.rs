@@ -0,0 +1,88 @@
+use chrono::NaiveDate;
+
+/// Rules for which dates can be checked or unchecked
+#[derive(Clone, Copy, Debug)]
+pub struct EditPolicy {
+    /// How many days before today can be edited without edit mode
+    pub grace_days: u32,
+    /// Whether edit mode unlocks dates after today
+    pub allow_future: bool,
+    /// Whether edits before the grace window need to be confirmed
+    pub confirm_old: bool,
+}
+
+/// Only today and yesterday, like a new config
+impl Default for EditPolicy {
+    fn default() -> Self {
+        Self {
+            grace_days: 1,
+            allow_future: false,
+            confirm_old: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Permission {
+    Allowed,
+    NeedsConfirmation,
+    Denied,
+}
+
+impl EditPolicy {
+    pub fn check(&self, date: NaiveDate, today: NaiveDate, edit_mode: bool) -> Permission {
+        let days_ago = today.signed_duration_since(date).num_days();
+        if days_ago < 0 {
+            if edit_mode && self.allow_future {
+                Permission::Allowed
+            } else {
+                Permission::Denied
+            }
+        } else if days_ago <= i64::from(self.grace_days) {
+            Permission::Allowed
+        } else if !edit_mode {
+            Permission::Denied
+        } else if self.confirm_old {
+            Permission::NeedsConfirmation
+        } else {
+            Permission::Allowed
+        }
+    }
+}
+
+#[test]
+fn test_edit_policy() {
+    let today = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+    let days_ago = |n| today - chrono::Duration::days(n);
+    let policy = EditPolicy {
+        grace_days: 3,
+        allow_future: false,
+        confirm_old: false,
+    };
+    for edit_mode in [false, true] {
+        for n in 0..=3 {
+            assert_eq!(
+                policy.check(days_ago(n), today, edit_mode),
+                Permission::Allowed
+            );
+        }
+        assert_eq!(
+            policy.check(days_ago(-1), today, edit_mode),
+            Permission::Denied
+        );
+    }
+    assert_eq!(policy.check(days_ago(4), today, false), Permission::Denied);
+    assert_eq!(policy.check(days_ago(4), today, true), Permission::Allowed);
+    let policy = EditPolicy {
+        grace_days: 0,
+        allow_future: true,
+        confirm_old: true,
+    };
+    assert_eq!(policy.check(days_ago(1), today, false), Permission::Denied);
+    assert_eq!(
+        policy.check(days_ago(1), today, true),
+        Permission::NeedsConfirmation
+    );
+    assert_eq!(policy.check(days_ago(-1), today, false), Permission::Denied);
+    assert_eq!(policy.check(days_ago(-1), today, true), Permission::Allowed);
+}
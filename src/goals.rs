@@ -0,0 +1,203 @@
+use crate::user_data::Activity;
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Something to work towards with an activity, written like "streak 30", "total 100"
+/// or "month 80%"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    /// Reach a streak of this many days
+    Streak(u32),
+    /// Check this many days in total
+    TotalDays(u32),
+    /// Complete this percentage of the days of the current month so far
+    MonthlyCompletion(u8),
+}
+
+impl Goal {
+    /// Current progress and the target of the goal
+    pub fn progress(&self, activity: &Activity, current_date: NaiveDate) -> (u32, u32) {
+        match *self {
+            Self::Streak(target) => (activity.current_streak(current_date), target),
+            Self::TotalDays(target) => {
+                // Like the statistics, ignore days outside of the tracked period
+                let done = activity.done_in(activity.starting_date..=current_date);
+                (done as u32, target)
+            }
+            Self::MonthlyCompletion(target) => {
+                let month_start = current_date
+                    .with_day(1)
+                    .unwrap()
+                    .max(activity.starting_date);
+                if month_start > current_date {
+                    return (0, u32::from(target));
+                }
+                let total = (current_date - month_start).num_days() + 1;
+                let done = activity.done_in(month_start..=current_date);
+                ((done as i64 * 100 / total) as u32, u32::from(target))
+            }
+        }
+    }
+    pub fn reached(&self, activity: &Activity, current_date: NaiveDate) -> bool {
+        let (progress, target) = self.progress(activity, current_date);
+        progress >= target
+    }
+    /// Parses a comma separated list of goals
+    pub fn parse_list(text: &str) -> Result<Vec<Self>, String> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|goal| !goal.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+    fn parse(text: &str) -> Result<Self, String> {
+        let err = || {
+            format!(
+                "Invalid goal \"{text}\", expected \"streak 30\", \"total 100\" or \"month 80%\""
+            )
+        };
+        let (kind, value) = text.split_once(' ').ok_or_else(err)?;
+        let value = value.trim();
+        match kind {
+            "streak" => Ok(Self::Streak(value.parse().map_err(|_| err())?)),
+            "total" => Ok(Self::TotalDays(value.parse().map_err(|_| err())?)),
+            "month" => {
+                let percent = value
+                    .strip_suffix('%')
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&percent| percent <= 100)
+                    .ok_or_else(err)?;
+                Ok(Self::MonthlyCompletion(percent))
+            }
+            _ => Err(err()),
+        }
+    }
+    /// Description of the goal for the side panel
+    pub fn describe(&self) -> String {
+        match *self {
+            Self::Streak(days) => format!("{days} day streak"),
+            Self::TotalDays(days) => format!("{days} days total"),
+            Self::MonthlyCompletion(percent) => format!("{percent}% this month"),
+        }
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Streak(days) => write!(f, "streak {days}"),
+            Self::TotalDays(days) => write!(f, "total {days}"),
+            Self::MonthlyCompletion(percent) => write!(f, "month {percent}%"),
+        }
+    }
+}
+
+/// Formats goals the way `Goal::parse_list` reads them
+pub fn format_list(goals: &[Goal]) -> String {
+    goals
+        .iter()
+        .map(Goal::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Which goals of `activity` are reached
+pub fn reached(activity: &Activity, current_date: NaiveDate) -> Vec<bool> {
+    activity
+        .goals
+        .iter()
+        .map(|goal| goal.reached(activity, current_date))
+        .collect()
+}
+
+/// The first goal of `activity` that is reached now, but wasn't according to `before`
+pub fn newly_reached(
+    activity: &Activity,
+    before: &[bool],
+    current_date: NaiveDate,
+) -> Option<Goal> {
+    activity
+        .goals
+        .iter()
+        .zip(before)
+        .find(|(goal, &was_reached)| !was_reached && goal.reached(activity, current_date))
+        .map(|(goal, _)| *goal)
+}
+
+#[cfg(test)]
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 3, day).unwrap()
+}
+
+#[test]
+fn test_goal_progress() {
+    let mut activity = Activity::new_default(date(1));
+    activity.dates.extend([date(1), date(2), date(4), date(5)]);
+    assert_eq!(Goal::Streak(3).progress(&activity, date(5)), (2, 3));
+    assert_eq!(Goal::TotalDays(10).progress(&activity, date(5)), (4, 10));
+    assert_eq!(
+        Goal::MonthlyCompletion(80).progress(&activity, date(5)),
+        (80, 80)
+    );
+    // Days of the month before the starting date don't count
+    activity.starting_date = date(4);
+    assert_eq!(
+        Goal::MonthlyCompletion(80).progress(&activity, date(5)),
+        (100, 80)
+    );
+    assert_eq!(
+        Goal::MonthlyCompletion(80).progress(&activity, date(3)),
+        (0, 80)
+    );
+    // Neither do days before the starting date or in the future, like imported ones
+    activity.dates.insert(date(9));
+    assert_eq!(Goal::TotalDays(10).progress(&activity, date(5)), (2, 10));
+}
+
+#[test]
+fn test_newly_reached() {
+    let mut activity = Activity::new_default(date(1));
+    activity.goals = vec![Goal::TotalDays(2), Goal::Streak(2)];
+    activity.dates.insert(date(1));
+    let before = reached(&activity, date(2));
+    assert_eq!(before, [false, false]);
+    activity.dates.insert(date(2));
+    assert_eq!(
+        newly_reached(&activity, &before, date(2)),
+        Some(Goal::TotalDays(2))
+    );
+    let before = reached(&activity, date(2));
+    assert_eq!(newly_reached(&activity, &before, date(2)), None);
+}
+
+#[test]
+fn test_negative_goal_progress() {
+    let mut activity = Activity::new_default(date(1));
+    activity.polarity = crate::user_data::Polarity::Negative;
+    activity.dates.insert(date(3));
+    assert_eq!(Goal::Streak(7).progress(&activity, date(5)), (2, 7));
+    assert_eq!(Goal::TotalDays(7).progress(&activity, date(5)), (4, 7));
+    assert_eq!(
+        Goal::MonthlyCompletion(80).progress(&activity, date(5)),
+        (80, 80)
+    );
+}
+
+#[test]
+fn test_parse_goals() {
+    let goals = Goal::parse_list("streak 30, total 100,month 80%,").unwrap();
+    assert_eq!(
+        goals,
+        [
+            Goal::Streak(30),
+            Goal::TotalDays(100),
+            Goal::MonthlyCompletion(80)
+        ]
+    );
+    assert_eq!(Goal::parse_list(&format_list(&goals)).unwrap(), goals);
+    assert_eq!(Goal::parse_list("").unwrap(), []);
+    for text in ["streak", "streak x", "month 80", "month 101%", "weekly 3"] {
+        assert!(Goal::parse_list(text).is_err(), "{text}");
+    }
+}
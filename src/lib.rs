@@ -0,0 +1,14 @@
+//! Data model, persistence and statistics of Calen-Do, without any user interface.
+//!
+//! The GUI binary is built on top of this, and so can other tools.
+
+#![warn(clippy::cast_lossless)]
+
+pub mod achievements;
+pub mod date_util;
+pub mod edit_policy;
+pub mod goals;
+pub mod server;
+pub mod stats;
+pub mod sync;
+pub mod user_data;
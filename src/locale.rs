@@ -0,0 +1,39 @@
+/// The parts of a locale tag like "en-US" or "hu_HU.UTF-8" that we care about
+#[derive(Debug, PartialEq, Eq)]
+pub struct Locale {
+    /// Lowercase language code, like "en"
+    pub language: String,
+    /// Uppercase region code, like "US"
+    pub region: Option<String>,
+}
+
+impl Locale {
+    pub fn parse(tag: &str) -> Self {
+        // Strip encoding and modifier, e.g. ".UTF-8" or "@euro"
+        let tag = tag.split(['.', '@']).next().unwrap_or_default();
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+        // Skip script subtags like "Hans" in "zh-Hans-CN"
+        let region = parts
+            .find(|part| part.len() == 2 || part.chars().all(|c| c.is_ascii_digit()))
+            .map(|part| part.to_ascii_uppercase());
+        Self { language, region }
+    }
+    pub fn system() -> Option<Self> {
+        sys_locale::get_locale().map(|tag| Self::parse(&tag))
+    }
+}
+
+#[test]
+fn test_parse_locale() {
+    let parse = |tag| {
+        let Locale { language, region } = Locale::parse(tag);
+        (language, region)
+    };
+    assert_eq!(parse("en-US"), ("en".into(), Some("US".into())));
+    assert_eq!(parse("hu_HU.UTF-8"), ("hu".into(), Some("HU".into())));
+    assert_eq!(parse("de_DE@euro"), ("de".into(), Some("DE".into())));
+    assert_eq!(parse("zh-Hans-CN"), ("zh".into(), Some("CN".into())));
+    assert_eq!(parse("fr"), ("fr".into(), None));
+    assert_eq!(parse("C"), ("c".into(), None));
+}
@@ -1,40 +1,202 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(feature = "gui", windows_subsystem = "windows")]
 #![warn(clippy::cast_lossless)]
 
-use crate::user_data::UserData;
-use chrono::prelude::*;
+use crate::config::Config;
+#[cfg(feature = "gui")]
+use crate::{locale::Locale, ui::Language};
+use calen_do::user_data::UserData;
+#[cfg(feature = "gui")]
+use calen_do::{
+    date_util::{self, WeekStart},
+    sync::{self, InstanceLock, SaveFile},
+    user_data::LoadError,
+};
+#[cfg(feature = "gui")]
+use chrono::NaiveDate;
 use directories::ProjectDirs;
-use std::error::Error;
+use std::{error::Error, path::PathBuf};
 
-mod date_util;
+mod cli;
+mod config;
+#[cfg(feature = "gui")]
+mod locale;
+#[cfg(feature = "gui")]
 mod ui;
-mod user_data;
 
-fn run() -> Result<(), Box<dyn Error>> {
+fn project_dirs() -> Result<ProjectDirs, Box<dyn Error>> {
     let dirs =
         ProjectDirs::from("", "crumblingstatue", "calen-do").ok_or("Can't create ProjectDirs")?;
-    let data_dir = dirs.data_dir();
-    if !data_dir.exists() {
-        std::fs::create_dir_all(data_dir)?;
+    for dir in [dirs.data_dir(), dirs.data_local_dir()] {
+        if !dir.exists() {
+            std::fs::create_dir_all(dir)?;
+        }
     }
-    let current_date = {
-        let date = Local::now().date_naive();
-        NaiveDate::from_ymd_opt(date.year(), date.month(), date.day()).unwrap()
+    Ok(dirs)
+}
+
+/// Where the files of Calen-Do are
+pub struct Paths {
+    /// The save file, which might be in a folder that is synced with other devices
+    pub save_file: PathBuf,
+    /// For the files that only concern this computer, like locks
+    pub local_dir: PathBuf,
+}
+
+/// Loads the config, and finds the save file: the one of `--test`, `--data` or the
+/// `save-file` setting, or the one in the data directory
+fn setup(test_mode: bool, data: Option<PathBuf>) -> Result<(Paths, Config), Box<dyn Error>> {
+    let dirs = project_dirs()?;
+    let config = Config::load(&dirs.config_dir().join("config.toml"))?;
+    let save_file = match data.or_else(|| config.save_file.clone()) {
+        Some(path) if !test_mode => path,
+        _ => UserData::save_path(dirs.data_dir(), test_mode),
+    };
+    let paths = Paths {
+        save_file,
+        local_dir: dirs.data_local_dir().to_owned(),
+    };
+    Ok((paths, config))
+}
+
+#[cfg(feature = "gui")]
+fn run_gui(paths: &Paths, config: Config) -> Result<(), Box<dyn Error>> {
+    let current_date = date_util::today();
+    let save_path = paths.save_file.clone();
+    let local = sync::local_files(&paths.local_dir, &save_path);
+    let instance = InstanceLock::acquire(&local)?;
+    if instance.is_none() && !confirm_read_only() {
+        sync::request_raise(&local)?;
+        return Ok(());
+    }
+    let mut save_file = SaveFile::new(save_path, &paths.local_dir);
+    let mut user_data = match load_or_new(&mut save_file, current_date, instance.is_some())? {
+        Some(data) => data,
+        None => return Ok(()),
     };
-    let test_mode = matches!(std::env::args().nth(1).as_deref(), Some("--test"));
-    let mut user_data = UserData::load_or_new(data_dir, current_date, test_mode);
-    ui::run(current_date, &mut user_data)?;
-    user_data.save(data_dir, test_mode)?;
+    let locale = Locale::system();
+    let week_start = config.week_start.unwrap_or_else(|| {
+        locale
+            .as_ref()
+            .and_then(|locale| locale.region.as_deref())
+            .map_or(WeekStart::Monday, WeekStart::for_region)
+    });
+    let language = config.language.unwrap_or_else(|| {
+        locale
+            .as_ref()
+            .and_then(|locale| Language::from_code(&locale.language))
+            .unwrap_or(Language::English)
+    });
+    ui::run(
+        current_date,
+        &mut user_data,
+        &config,
+        week_start,
+        language,
+        &mut save_file,
+        instance.as_ref(),
+    )?;
+    if instance.is_some() {
+        save_file.save(&mut user_data)?;
+    }
     Ok(())
 }
 
+/// Asks whether to open another window while Calen-Do is already running
+#[cfg(feature = "gui")]
+fn confirm_read_only() -> bool {
+    rfd::MessageDialog::new()
+        .set_title("Calen-Do is already running")
+        .set_description(
+            "Calen-Do is already running.\n\n\
+            Yes: open another window read-only, to look at the data without changing it.\n\
+            No: switch to the running window.",
+        )
+        .set_level(rfd::MessageLevel::Warning)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+}
+
+/// Loads the save file, or new data if there is none yet.
+///
+/// A damaged save file can be replaced after moving it to a backup, `None` if that's
+/// declined. Other errors are returned, so a file that might be fine is never overwritten.
+#[cfg(feature = "gui")]
+fn load_or_new(
+    save_file: &mut SaveFile,
+    current_date: NaiveDate,
+    writable: bool,
+) -> Result<Option<UserData>, Box<dyn Error>> {
+    match save_file.load() {
+        Ok(data) => Ok(Some(data)),
+        // First run, nothing to warn about
+        Err(LoadError::Missing) => Ok(Some(UserData::new_default(current_date))),
+        Err(e @ LoadError::Corrupt { .. }) if writable => {
+            let msg = format!(
+                "The user data in {} is damaged: {}\n\n\
+                Yes: move it to {} and start with new user data.\n\
+                No: quit without changing it.",
+                save_file.path().display(),
+                e,
+                save_file.backup_path().display()
+            );
+            let replace = rfd::MessageDialog::new()
+                .set_title("Damaged user data")
+                .set_description(&msg)
+                .set_level(rfd::MessageLevel::Warning)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if !replace {
+                return Ok(None);
+            }
+            save_file.back_up()?;
+            Ok(Some(UserData::new_default(current_date)))
+        }
+        Err(e) => Err(format!(
+            "Error loading user data from {}: {}",
+            save_file.path().display(),
+            e
+        )
+        .into()),
+    }
+}
+
 fn main() {
-    let result = run();
-    if let Err(e) = result {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let n_args = args.len();
+    args.retain(|arg| arg != "--test");
+    let test_mode = args.len() != n_args;
+    let data = match args.iter().position(|arg| arg == "--data") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.remove(i + 1);
+            args.remove(i);
+            Some(PathBuf::from(path))
+        }
+        Some(_) => {
+            eprintln!("Error: --data needs a save file\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+        None => None,
+    };
+    let setup = setup(test_mode, data);
+    if !args.is_empty() {
+        if let Err(e) = setup.and_then(|(paths, config)| cli::run(&args, &paths, &config)) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    #[cfg(feature = "gui")]
+    if let Err(e) = setup.and_then(|(paths, config)| run_gui(&paths, config)) {
         rfd::MessageDialog::new()
             .set_title("Fatal error")
             .set_description(&format!("Fatal error: {e}"))
             .set_level(rfd::MessageLevel::Error)
             .show();
     }
+    #[cfg(not(feature = "gui"))]
+    {
+        eprintln!("Built without the calendar window, a command is needed.\n");
+        eprintln!("{}", cli::USAGE);
+        std::process::exit(2);
+    }
 }
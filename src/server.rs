@@ -0,0 +1,561 @@
+//! A small HTTP server with a JSON API, for integrations like phone shortcuts, home
+//! automation or editor plugins.
+//!
+//! Every request needs an `Authorization: Bearer <token>` header.
+//!
+//! - `GET /activities`: all activities, with their streaks
+//! - `GET /activities/<id>`: an activity, with its checked days
+//! - `GET /activities/<id>/stats`: statistics of an activity
+//! - `PUT /activities/<id>/dates/<YYYY-MM-DD>`: check a day
+//! - `DELETE /activities/<id>/dates/<YYYY-MM-DD>`: uncheck a day
+//!
+//! Checking and unchecking follow the same `EditPolicy` as the calendar window. Add `?edit`
+//! to edit like in edit mode, and `&confirm` to confirm editing days before the edit window.
+//! Edits that aren't allowed get a 403 response, unconfirmed ones a 409.
+
+use crate::{
+    date_util,
+    edit_policy::{EditPolicy, Permission},
+    stats::{self, Rate, RECENT_WINDOWS},
+    sync::SaveFile,
+    user_data::{self, Activity, ActivityId, UserData},
+};
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, prelude::*, BufReader},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+/// Only reachable from this computer. Listen on another address to allow other devices.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8417";
+/// Longest accepted request head, requests don't have bodies
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// The bearer token of the `Authorization` header
+    pub token: Option<String>,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+/// Reads the token from `path`, or creates a new random one there
+pub fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) => return Ok(token.trim().to_owned()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let mut random = [0; 16];
+    getrandom::getrandom(&mut random).map_err(io::Error::from)?;
+    let token: String = random.iter().map(|byte| format!("{byte:02x}")).collect();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Only readable by the user, like an SSH key
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    writeln!(options.open(path)?, "{token}")?;
+    Ok(token)
+}
+
+/// The save file and its data, shared by the connections
+type Shared = Arc<Mutex<(SaveFile, UserData)>>;
+
+/// The token and edit rules, the same for all connections
+struct Settings {
+    token: String,
+    edit_policy: EditPolicy,
+}
+
+/// Answers requests on `listener`, keeping `save_file` and `data` in sync.
+///
+/// Each connection gets its own thread, so a slow client doesn't hold up the others.
+pub fn serve(
+    listener: TcpListener,
+    token: &str,
+    edit_policy: EditPolicy,
+    save_file: SaveFile,
+    data: UserData,
+) {
+    let settings = Arc::new(Settings {
+        token: token.to_owned(),
+        edit_policy,
+    });
+    let shared = Arc::new(Mutex::new((save_file, data)));
+    for stream in listener.incoming() {
+        // Like running out of file descriptors, which might only be temporary
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Can't accept a connection: {e}");
+                continue;
+            }
+        };
+        let settings = Arc::clone(&settings);
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            // A failing client shouldn't stop the server
+            let _ = handle_connection(stream, &settings, &shared);
+        });
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    settings: &Settings,
+    shared: &Shared,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let response = match read_request(BufReader::new(&stream).take(MAX_REQUEST_LEN)) {
+        Ok(request) if !authorized(&request, &settings.token) => {
+            Response::error(401, "Missing or wrong token")
+        }
+        Ok(request) => {
+            // Another request panicking doesn't leave the data half changed, it's only
+            // changed after all checks
+            let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+            let (save_file, data) = &mut *shared;
+            respond(&request, &settings.edit_policy, save_file, data)
+        }
+        Err(_) => Response::error(400, "Invalid request"),
+    };
+    write_response(&mut stream, &response)
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    match &request.token {
+        Some(given) => token_matches(given, token),
+        None => false,
+    }
+}
+
+/// Compares in constant time, so the time taken doesn't tell how much of a guess is right
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Answers `request` with the latest data, and saves any changes
+fn respond(
+    request: &Request,
+    edit_policy: &EditPolicy,
+    save_file: &mut SaveFile,
+    data: &mut UserData,
+) -> Response {
+    if let Err(e) = save_file.sync(data) {
+        return Response::error(500, &format!("Can't load the save file: {e}"));
+    }
+    let current_date = date_util::today();
+    for activity in &mut data.activities {
+        activity.update_freezes(current_date);
+    }
+    let before = data.clone();
+    let (response, modified) = handle(request, edit_policy, data, current_date);
+    if modified {
+        if let Err(e) = save_file.save(data) {
+            // Don't serve or build on a change that isn't saved
+            *data = before;
+            return Response::error(500, &format!("Can't save: {e}"));
+        }
+    }
+    response
+}
+
+/// Answers `request`, and tells whether `data` was modified
+pub fn handle(
+    request: &Request,
+    edit_policy: &EditPolicy,
+    data: &mut UserData,
+    current_date: NaiveDate,
+) -> (Response, bool) {
+    let (path, query) = request.path.split_once('?').unwrap_or((&request.path, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let flag = |name| query.split('&').any(|param| param == name);
+    let find = |data: &UserData, id: &str| {
+        id.parse::<ActivityId>()
+            .ok()
+            .and_then(|id| data.activity_index(id))
+    };
+    match (request.method.as_str(), &segments[..]) {
+        ("GET", ["activities"]) => {
+            let activities = data
+                .activities
+                .iter()
+                .map(|a| summary(a, current_date))
+                .collect();
+            (Response::ok(Value::Array(activities)), false)
+        }
+        ("GET", ["activities", id]) => match find(data, id) {
+            Some(idx) => {
+                let activity = &data.activities[idx];
+                let mut body = summary(activity, current_date);
+                body["dates"] = json!(activity.dates);
+                (Response::ok(body), false)
+            }
+            None => (Response::error(404, "No such activity"), false),
+        },
+        ("GET", ["activities", id, "stats"]) => match find(data, id) {
+            Some(idx) => (
+                Response::ok(activity_stats(&data.activities[idx], current_date)),
+                false,
+            ),
+            None => (Response::error(404, "No such activity"), false),
+        },
+        (method @ ("PUT" | "DELETE"), ["activities", id, "dates", date]) => {
+            let idx = match find(data, id) {
+                Some(idx) => idx,
+                None => return (Response::error(404, "No such activity"), false),
+            };
+            let date = match date.parse::<NaiveDate>() {
+                Ok(date) => date,
+                Err(_) => {
+                    return (
+                        Response::error(400, "Invalid date, expected YYYY-MM-DD"),
+                        false,
+                    )
+                }
+            };
+            if !user_data::can_store_date(date) || date < data.activities[idx].starting_date {
+                return (
+                    Response::error(400, "The date is outside of the tracked period"),
+                    false,
+                );
+            }
+            match edit_policy.check(date, current_date, flag("edit")) {
+                Permission::Allowed => {}
+                Permission::NeedsConfirmation if flag("confirm") => {}
+                Permission::NeedsConfirmation => {
+                    return (
+                        Response::error(409, "Editing this day needs &confirm"),
+                        false,
+                    )
+                }
+                Permission::Denied => {
+                    return (Response::error(403, "This day can't be edited"), false)
+                }
+            }
+            let activity = &mut data.activities[idx];
+            let modified = if method == "PUT" {
+                activity.dates.insert(date)
+            } else {
+                activity.dates.remove(&date)
+            };
+            activity.update_freezes(current_date);
+            data.achievements.update(&data.activities);
+            let activity = &data.activities[idx];
+            let mut body = summary(activity, current_date);
+            body["checked"] = json!(activity.dates.contains(&date));
+            (Response::ok(body), modified)
+        }
+        (_, ["activities", ..]) => (Response::error(405, "Method not allowed"), false),
+        _ => (Response::error(404, "Not found"), false),
+    }
+}
+
+fn summary(activity: &Activity, current_date: NaiveDate) -> Value {
+    json!({
+        // As a string, JSON numbers can't hold every u64 in some languages
+        "id": activity.id.to_string(),
+        "name": activity.name,
+        "group": activity.group,
+        "negative": activity.is_negative(),
+        "starting_date": activity.starting_date,
+        "current_streak": activity.current_streak(current_date),
+        "longest_streak": activity.longest_streak(current_date),
+        "days_checked": activity.dates.len(),
+    })
+}
+
+fn activity_stats(activity: &Activity, current_date: NaiveDate) -> Value {
+    let start = activity.starting_date;
+    // Like in the calendar, the statistics of negative habits are about clean days
+    let stats = if activity.is_negative() {
+        stats::compute(&activity.clean_days(current_date), start, current_date)
+    } else {
+        stats::compute(&activity.dates, start, current_date)
+    };
+    let rate = |rate: &Rate| json!({ "done": rate.done, "total": rate.total });
+    let recent: serde_json::Map<String, Value> = RECENT_WINDOWS
+        .iter()
+        .zip(&stats.recent)
+        .map(|(days, r)| (days.to_string(), rate(r)))
+        .collect();
+    json!({
+        "overall": rate(&stats.overall),
+        "recent": recent,
+        "streak_count": stats.streak_count,
+        "average_streak": stats.average_streak,
+        "median_streak": stats.median_streak,
+        "current_streak": activity.current_streak(current_date),
+        "longest_streak": activity.longest_streak(current_date),
+    })
+}
+
+fn read_request<R: BufRead>(mut reader: R) -> io::Result<Request> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid request");
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+        _ => return Err(invalid()),
+    };
+    let mut token = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            // The head has to end with an empty line
+            return Err(invalid());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                token = value.trim().strip_prefix("Bearer ").map(str::to_owned);
+            }
+        }
+    }
+    Ok(Request {
+        method,
+        path,
+        token,
+    })
+}
+
+fn write_response<W: Write>(w: &mut W, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = response.body.to_string();
+    write!(
+        w,
+        "HTTP/1.1 {} {reason}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        response.status,
+        body.len()
+    )?;
+    w.flush()
+}
+
+#[cfg(test)]
+fn request(method: &str, path: &str) -> Request {
+    Request {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        token: None,
+    }
+}
+
+#[test]
+fn test_handle() {
+    let date = |day| NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+    let mut data = UserData::new_default(date(1));
+    let id = data.activities[0].id.to_string();
+    let policy = EditPolicy::default();
+    let (response, modified) = handle(&request("GET", "/activities"), &policy, &mut data, date(3));
+    assert_eq!((response.status, modified), (200, false));
+    assert_eq!(response.body[0]["id"], id);
+    let check = format!("/activities/{id}/dates/2023-03-03");
+    let (response, modified) = handle(&request("PUT", &check), &policy, &mut data, date(3));
+    assert_eq!((response.status, modified), (200, true));
+    assert_eq!(response.body["checked"], true);
+    assert_eq!(response.body["current_streak"], 1);
+    // Checking again changes nothing
+    let (_, modified) = handle(&request("PUT", &check), &policy, &mut data, date(3));
+    assert!(!modified);
+    let stats = format!("/activities/{id}/stats");
+    let (response, _) = handle(&request("GET", &stats), &policy, &mut data, date(3));
+    assert_eq!(response.body["overall"]["done"], 1);
+    assert_eq!(response.body["overall"]["total"], 3);
+    let (response, modified) = handle(&request("DELETE", &check), &policy, &mut data, date(3));
+    assert_eq!((response.status, modified), (200, true));
+    assert!(data.activities[0].dates.is_empty());
+    for (method, path, status) in [
+        ("GET", "/activities/123", 404),
+        ("PUT", "/activities/nope/dates/2023-03-03", 404),
+        ("PUT", &*format!("/activities/{id}/dates/2023-13-03"), 400),
+        // Edit mode doesn't allow days the save file can't store, or before the start
+        (
+            "PUT",
+            &*format!("/activities/{id}/dates/-0001-01-01?edit"),
+            400,
+        ),
+        (
+            "PUT",
+            &*format!("/activities/{id}/dates/2023-02-28?edit"),
+            400,
+        ),
+        // Like in the calendar, only today and yesterday outside of edit mode
+        ("PUT", &*format!("/activities/{id}/dates/2023-03-04"), 403),
+        ("PUT", &*format!("/activities/{id}/dates/2023-03-01"), 403),
+        (
+            "DELETE",
+            &*format!("/activities/{id}/dates/2023-03-01"),
+            403,
+        ),
+        (
+            "PUT",
+            &*format!("/activities/{id}/dates/2023-03-04?edit"),
+            403,
+        ),
+        ("POST", "/activities", 405),
+        ("GET", "/", 404),
+    ] {
+        let (response, modified) = handle(&request(method, path), &policy, &mut data, date(3));
+        assert_eq!(
+            (response.status, modified),
+            (status, false),
+            "{method} {path}"
+        );
+    }
+    let old = format!("/activities/{id}/dates/2023-03-01");
+    let (response, modified) = handle(
+        &request("PUT", &format!("{old}?edit")),
+        &policy,
+        &mut data,
+        date(3),
+    );
+    assert_eq!((response.status, modified), (200, true));
+    let policy = EditPolicy {
+        confirm_old: true,
+        ..policy
+    };
+    let (response, modified) = handle(
+        &request("DELETE", &format!("{old}?edit")),
+        &policy,
+        &mut data,
+        date(3),
+    );
+    assert_eq!((response.status, modified), (409, false));
+    let confirmed = format!("{old}?edit&confirm");
+    let (response, modified) = handle(&request("DELETE", &confirmed), &policy, &mut data, date(3));
+    assert_eq!((response.status, modified), (200, true));
+}
+
+#[test]
+fn test_token_matches() {
+    assert!(token_matches("0123abcd", "0123abcd"));
+    assert!(!token_matches("0123abce", "0123abcd"));
+    assert!(!token_matches("0123abc", "0123abcd"));
+    assert!(!token_matches("", "0123abcd"));
+}
+
+#[test]
+fn test_failed_save_is_rolled_back() {
+    let mut save_file = SaveFile::new(
+        Path::new("no-such-dir").join("calen-do.dat"),
+        Path::new("no-such-dir"),
+    );
+    let today = date_util::today();
+    let mut data = UserData::new_default(today);
+    let id = data.activities[0].id;
+    let check = request("PUT", &format!("/activities/{id}/dates/{today}"));
+    let response = respond(&check, &EditPolicy::default(), &mut save_file, &mut data);
+    assert_eq!(response.status, 500);
+    assert!(data.activities[0].dates.is_empty());
+}
+
+#[test]
+fn test_serve() {
+    let dir = std::env::temp_dir().join(format!("calen-do-server-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("calen-do.dat");
+    let token = load_or_create_token(&dir.join("calen-do.token")).unwrap();
+    assert_eq!(token.len(), 32);
+    assert_eq!(
+        load_or_create_token(&dir.join("calen-do.token")).unwrap(),
+        token
+    );
+    let mut save_file = SaveFile::new(path.clone(), &dir);
+    let mut data = UserData::new_default(date_util::today());
+    save_file.save(&mut data).unwrap();
+    let id = data.activities[0].id;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_token = token.clone();
+    thread::spawn(move || {
+        serve(
+            listener,
+            &server_token,
+            EditPolicy::default(),
+            save_file,
+            data,
+        )
+    });
+    let send = |method: &str, path: &str, token: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    assert!(send("GET", "/activities", "wrong").starts_with("HTTP/1.1 401"));
+    let today = date_util::today();
+    let response = send("PUT", &format!("/activities/{id}/dates/{today}"), &token);
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let body: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["current_streak"], 1);
+    // The change is saved right away
+    let mut other = SaveFile::new(path.clone(), &dir);
+    let mut other_data = other.load().unwrap();
+    assert!(other_data.activities[0].dates.contains(&today));
+    let response = send("DELETE", &format!("/activities/{id}/dates/{today}"), &token);
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    // Another instance that still has the day checked doesn't bring it back when it saves
+    other_data.activities[0].name = "Renamed".to_owned();
+    other.save(&mut other_data).unwrap();
+    assert!(other_data.activities[0].dates.is_empty());
+    let saved = UserData::load(&path).unwrap();
+    assert!(saved.activities[0].dates.is_empty());
+    let response = send("GET", &format!("/activities/{id}"), &token);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let body: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["name"], "Renamed");
+    assert_eq!(body["dates"], json!([]));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,728 @@
+use crate::{date_util::DAYS_PER_WEEK, user_data::Activity};
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::RangeInclusive,
+};
+
+/// A set of dates, ordered so that streaks can be found without visiting every day
+pub trait HasDate {
+    fn has_date(&self, date: NaiveDate) -> bool;
+    /// The dates within `range`, in ascending order
+    fn dates_in(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Box<dyn DoubleEndedIterator<Item = NaiveDate> + '_>;
+}
+
+impl<V> HasDate for BTreeMap<NaiveDate, V> {
+    fn has_date(&self, date: NaiveDate) -> bool {
+        self.contains_key(&date)
+    }
+    fn dates_in(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Box<dyn DoubleEndedIterator<Item = NaiveDate> + '_> {
+        // `range` panics on start > end
+        if range.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(self.range(range).map(|(date, _)| *date))
+    }
+}
+
+impl HasDate for BTreeSet<NaiveDate> {
+    fn has_date(&self, date: NaiveDate) -> bool {
+        self.contains(&date)
+    }
+    fn dates_in(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Box<dyn DoubleEndedIterator<Item = NaiveDate> + '_> {
+        if range.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+        Box::new(self.range(range).copied())
+    }
+}
+
+/// The dates of two sets, without building their union
+pub struct Union<'a, A, B>(pub &'a A, pub &'a B);
+
+impl<A: HasDate, B: HasDate> HasDate for Union<'_, A, B> {
+    fn has_date(&self, date: NaiveDate) -> bool {
+        self.0.has_date(date) || self.1.has_date(date)
+    }
+    fn dates_in(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Box<dyn DoubleEndedIterator<Item = NaiveDate> + '_> {
+        Box::new(MergeDates {
+            a: self.0.dates_in(range.clone()),
+            b: self.1.dates_in(range),
+            a_front: None,
+            b_front: None,
+            a_back: None,
+            b_back: None,
+        })
+    }
+}
+
+/// Merges two ascending date iterators from both ends, skipping duplicates
+struct MergeDates<'a> {
+    a: Box<dyn DoubleEndedIterator<Item = NaiveDate> + 'a>,
+    b: Box<dyn DoubleEndedIterator<Item = NaiveDate> + 'a>,
+    /// Dates taken from the front or back of an iterator, but not returned yet
+    a_front: Option<NaiveDate>,
+    b_front: Option<NaiveDate>,
+    a_back: Option<NaiveDate>,
+    b_back: Option<NaiveDate>,
+}
+
+impl Iterator for MergeDates<'_> {
+    type Item = NaiveDate;
+    fn next(&mut self) -> Option<NaiveDate> {
+        // Once an iterator is used up, the date taken from its back is its first
+        let a = self
+            .a_front
+            .take()
+            .or_else(|| self.a.next())
+            .or_else(|| self.a_back.take());
+        let b = self
+            .b_front
+            .take()
+            .or_else(|| self.b.next())
+            .or_else(|| self.b_back.take());
+        match (a, b) {
+            (Some(a), Some(b)) if a < b => {
+                self.b_front = Some(b);
+                Some(a)
+            }
+            (Some(a), Some(b)) if b < a => {
+                self.a_front = Some(a);
+                Some(b)
+            }
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl DoubleEndedIterator for MergeDates<'_> {
+    fn next_back(&mut self) -> Option<NaiveDate> {
+        let a = self
+            .a_back
+            .take()
+            .or_else(|| self.a.next_back())
+            .or_else(|| self.a_front.take());
+        let b = self
+            .b_back
+            .take()
+            .or_else(|| self.b.next_back())
+            .or_else(|| self.b_front.take());
+        match (a, b) {
+            (Some(a), Some(b)) if a > b => {
+                self.b_back = Some(b);
+                Some(a)
+            }
+            (Some(a), Some(b)) if b > a => {
+                self.a_back = Some(a);
+                Some(b)
+            }
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+pub fn find_current_streak<T: HasDate>(dates: &T, current_date: NaiveDate) -> u32 {
+    let mut expected = Some(current_date);
+    // Count down from current date, until we find an unfilled day
+    dates
+        .dates_in(NaiveDate::MIN..=current_date)
+        .rev()
+        .take_while(|&date| {
+            let consecutive = Some(date) == expected;
+            expected = date.pred_opt();
+            consecutive
+        })
+        .count() as u32
+}
+
+/// Days without a mark up to `current_date`, since the last marked day or `start`.
+///
+/// This is the current streak of negative habits, where marked days are relapses.
+pub fn find_current_gap<T: HasDate>(dates: &T, start: NaiveDate, current_date: NaiveDate) -> u32 {
+    let clean_from = match dates.dates_in(start..=current_date).next_back() {
+        Some(last) => match last.succ_opt() {
+            Some(next) => next,
+            None => return 0,
+        },
+        None => start,
+    };
+    if clean_from > current_date {
+        0
+    } else {
+        (current_date - clean_from).num_days() as u32 + 1
+    }
+}
+
+/// The most days without a mark between `start` and `current_date`
+pub fn find_longest_gap<T: HasDate>(dates: &T, start: NaiveDate, current_date: NaiveDate) -> u32 {
+    if start > current_date {
+        return 0;
+    }
+    let mut longest = 0;
+    let mut clean_from = start;
+    for date in dates.dates_in(start..=current_date) {
+        longest = longest.max((date - clean_from).num_days() as u32);
+        clean_from = match date.succ_opt() {
+            Some(next) => next,
+            None => return longest,
+        };
+    }
+    if clean_from <= current_date {
+        longest = longest.max((current_date - clean_from).num_days() as u32 + 1);
+    }
+    longest
+}
+
+/// Consecutive days needed to earn a streak freeze
+pub const FREEZE_EARN_DAYS: u32 = 7;
+/// How many unused freezes can be kept at most
+pub const MAX_FREEZES: u8 = 2;
+
+/// Streak freezes of an activity. A freeze covers a missed day, so the streak goes on.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Freezes {
+    /// Earned freezes that weren't used yet
+    pub available: u8,
+    /// Missed days covered by a freeze
+    pub covered: BTreeSet<NaiveDate>,
+}
+
+/// Earn a freeze for every `FREEZE_EARN_DAYS` consecutive days done since `start`,
+/// and use them up on the missed days of the streak.
+///
+/// Freezes are only used on a gap they can cover completely, a longer gap breaks the
+/// streak without costing any. The current day doesn't count as missed, it can still be done.
+pub fn compute_freezes(
+    dates: &BTreeSet<NaiveDate>,
+    start: NaiveDate,
+    current_date: NaiveDate,
+) -> Freezes {
+    let mut freezes = Freezes::default();
+    let mut progress = 0;
+    let mut prev: Option<NaiveDate> = None;
+    // Go gap by gap like `find_longest_streak`, with `None` for the end of the dates
+    for date in dates.dates_in(start..=current_date).map(Some).chain([None]) {
+        if let Some(prev) = prev {
+            // The current day isn't missed yet
+            let missed = (date.unwrap_or(current_date) - prev).num_days() - 1;
+            if missed > i64::from(freezes.available) {
+                // The streak breaks
+                progress = 0;
+            } else if missed > 0 {
+                freezes
+                    .covered
+                    .extend(prev.iter_days().skip(1).take(missed as usize));
+                freezes.available -= missed as u8;
+            }
+        }
+        let date = match date {
+            Some(date) => date,
+            None => break,
+        };
+        progress += 1;
+        if progress == FREEZE_EARN_DAYS {
+            freezes.available = (freezes.available + 1).min(MAX_FREEZES);
+            progress = 0;
+        }
+        prev = Some(date);
+    }
+    freezes
+}
+
+pub fn find_longest_streak<T: HasDate>(
+    start: NaiveDate,
+    dates: &T,
+    current_date: NaiveDate,
+) -> u32 {
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut prev: Option<NaiveDate> = None;
+    for date in dates.dates_in(start..=current_date) {
+        if prev.and_then(|prev| prev.succ_opt()) == Some(date) {
+            streak += 1;
+        } else {
+            streak = 1;
+        }
+        longest = longest.max(streak);
+        prev = Some(date);
+    }
+    longest
+}
+
+/// How many of the activities that had started by `date` were done on it
+pub fn day_completion(activities: &[&Activity], date: NaiveDate) -> Rate {
+    let mut rate = Rate::default();
+    for activity in activities.iter().filter(|a| a.starting_date <= date) {
+        rate.add(activity.dates.contains(&date));
+    }
+    rate
+}
+
+/// Days on which every activity that had started was done
+pub fn perfect_days(activities: &[&Activity]) -> BTreeSet<NaiveDate> {
+    // The earliest started activity is active on every day that can be perfect,
+    // so only its days need to be checked
+    let earliest = match activities.iter().min_by_key(|a| a.starting_date) {
+        Some(activity) => activity,
+        None => return BTreeSet::new(),
+    };
+    earliest
+        .dates
+        .range(earliest.starting_date..)
+        .copied()
+        .filter(|&date| {
+            let rate = day_completion(activities, date);
+            rate.done == rate.total
+        })
+        .collect()
+}
+
+/// Lengths of the last N days that completion rates are calculated for
+pub const RECENT_WINDOWS: [u32; 4] = [7, 30, 90, 365];
+
+/// Length of the window for the rolling completion rate
+pub const ROLLING_WINDOW: usize = 30;
+
+/// How many of a number of days were done
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rate {
+    pub done: u32,
+    pub total: u32,
+}
+
+impl Rate {
+    fn add(&mut self, done: bool) {
+        self.total += 1;
+        if done {
+            self.done += 1;
+        }
+    }
+    /// The completion rate between 0 and 1, or `None` if there were no days
+    pub fn fraction(&self) -> Option<f32> {
+        (self.total != 0).then(|| self.done as f32 / self.total as f32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BestMonth {
+    pub year: i32,
+    pub month: u32,
+    pub done: u32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Completion since the starting date
+    pub overall: Rate,
+    /// Completion over each of the `RECENT_WINDOWS`
+    pub recent: [Rate; RECENT_WINDOWS.len()],
+    pub streak_count: u32,
+    pub average_streak: f32,
+    pub median_streak: f32,
+    /// The month with the most days done, the latest one if there are ties
+    pub best_month: Option<BestMonth>,
+    /// Completion by weekday, starting with Monday
+    pub weekdays: [Rate; DAYS_PER_WEEK as usize],
+    /// Completion rate of the last `ROLLING_WINDOW` days for every day since the start.
+    ///
+    /// The first days average over fewer days, as there is no history before the start.
+    pub rolling_rate: Vec<f32>,
+}
+
+/// Calculate statistics of the days between `start` and `current_date`
+pub fn compute<T: HasDate>(dates: &T, start: NaiveDate, current_date: NaiveDate) -> Stats {
+    let mut stats = Stats::default();
+    let mut streaks = Vec::new();
+    let mut streak = 0;
+    let mut month = None;
+    // Number of days done up to and including each day
+    let mut done_sums = Vec::new();
+    for date in start.iter_days().take_while(|&date| date <= current_date) {
+        let done = dates.has_date(date);
+        stats.overall.add(done);
+        done_sums.push(stats.overall.done);
+        let n_days = done_sums.len();
+        let done_before_window = if n_days > ROLLING_WINDOW {
+            done_sums[n_days - ROLLING_WINDOW - 1]
+        } else {
+            0
+        };
+        stats.rolling_rate.push(
+            (stats.overall.done - done_before_window) as f32 / n_days.min(ROLLING_WINDOW) as f32,
+        );
+        let days_ago = current_date.signed_duration_since(date).num_days();
+        for (rate, window) in stats.recent.iter_mut().zip(RECENT_WINDOWS) {
+            if days_ago < i64::from(window) {
+                rate.add(done);
+            }
+        }
+        stats.weekdays[date.weekday().num_days_from_monday() as usize].add(done);
+        if done {
+            streak += 1;
+        } else if streak != 0 {
+            streaks.push(streak);
+            streak = 0;
+        }
+        // Count days per month, and compare when the month is over
+        let (year, month_idx) = (date.year(), date.month());
+        let current = month.get_or_insert(BestMonth {
+            year,
+            month: month_idx,
+            done: 0,
+        });
+        if (current.year, current.month) != (year, month_idx) {
+            update_best_month(&mut stats.best_month, *current);
+            *current = BestMonth {
+                year,
+                month: month_idx,
+                done: 0,
+            };
+        }
+        if done {
+            current.done += 1;
+        }
+    }
+    if streak != 0 {
+        streaks.push(streak);
+    }
+    if let Some(month) = month {
+        update_best_month(&mut stats.best_month, month);
+    }
+    stats.streak_count = streaks.len() as u32;
+    if !streaks.is_empty() {
+        stats.average_streak = streaks.iter().sum::<u32>() as f32 / streaks.len() as f32;
+        streaks.sort_unstable();
+        let mid = streaks.len() / 2;
+        stats.median_streak = if streaks.len() % 2 == 0 {
+            (streaks[mid - 1] + streaks[mid]) as f32 / 2.0
+        } else {
+            streaks[mid] as f32
+        };
+    }
+    stats
+}
+
+fn update_best_month(best: &mut Option<BestMonth>, month: BestMonth) {
+    if month.done != 0 && !matches!(best, Some(best) if best.done > month.done) {
+        *best = Some(month);
+    }
+}
+
+#[cfg(test)]
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn test_compute_stats() {
+    let start = date(2023, 1, 28);
+    let today = date(2023, 2, 10);
+    // Streaks of 2 (Jan 28-29), 3 (Feb 1-3) and 4 (Feb 7-10)
+    let dates: BTreeSet<NaiveDate> = [
+        (1, 28),
+        (1, 29),
+        (2, 1),
+        (2, 2),
+        (2, 3),
+        (2, 7),
+        (2, 8),
+        (2, 9),
+        (2, 10),
+    ]
+    .into_iter()
+    .map(|(m, d)| date(2023, m, d))
+    .collect();
+    let stats = compute(&dates, start, today);
+    assert_eq!(stats.overall, Rate { done: 9, total: 14 });
+    assert_eq!(stats.recent[0], Rate { done: 4, total: 7 });
+    // Windows longer than the history only count days since the start
+    assert_eq!(stats.recent[1], Rate { done: 9, total: 14 });
+    assert_eq!(stats.streak_count, 3);
+    assert_eq!(stats.average_streak, 3.0);
+    assert_eq!(stats.median_streak, 3.0);
+    assert_eq!(
+        stats.best_month,
+        Some(BestMonth {
+            year: 2023,
+            month: 2,
+            done: 7
+        })
+    );
+    // Two weeks, so there are two of each weekday
+    let sunday = Weekday::Sun.num_days_from_monday() as usize;
+    assert_eq!(stats.weekdays[sunday], Rate { done: 1, total: 2 });
+    let friday = Weekday::Fri.num_days_from_monday() as usize;
+    assert_eq!(stats.weekdays[friday], Rate { done: 2, total: 2 });
+}
+
+#[test]
+fn test_rolling_rate() {
+    let start = date(2023, 1, 1);
+    let today = start + chrono::Duration::days(ROLLING_WINDOW as i64 * 2 - 1);
+    // Only the first half of the history is done
+    let dates: BTreeSet<NaiveDate> = start.iter_days().take(ROLLING_WINDOW).collect();
+    let stats = compute(&dates, start, today);
+    assert_eq!(stats.rolling_rate.len(), ROLLING_WINDOW * 2);
+    assert!(stats.rolling_rate[..ROLLING_WINDOW]
+        .iter()
+        .all(|&r| r == 1.0));
+    assert_eq!(
+        stats.rolling_rate[ROLLING_WINDOW],
+        (ROLLING_WINDOW - 1) as f32 / ROLLING_WINDOW as f32
+    );
+    assert_eq!(*stats.rolling_rate.last().unwrap(), 0.0);
+    // Days before the start don't count as missed
+    let stats = compute(
+        &BTreeSet::from([start]),
+        start,
+        start + chrono::Duration::days(1),
+    );
+    assert_eq!(stats.rolling_rate, [1.0, 0.5]);
+}
+
+#[test]
+fn test_compute_stats_edge_cases() {
+    let today = date(2023, 2, 10);
+    let empty = compute(&BTreeSet::new(), today, today);
+    assert_eq!(empty.overall, Rate { done: 0, total: 1 });
+    assert_eq!(empty.streak_count, 0);
+    assert_eq!(empty.best_month, None);
+    assert_eq!(empty.overall.fraction(), Some(0.0));
+    // Starting date in the future
+    let future = compute(&BTreeSet::from([today]), date(2023, 3, 1), today);
+    assert_eq!(future.overall.fraction(), None);
+    // Even number of streaks: 1 and 2
+    let dates = BTreeSet::from([date(2023, 2, 7), date(2023, 2, 9), date(2023, 2, 10)]);
+    let stats = compute(&dates, date(2023, 2, 1), today);
+    assert_eq!(stats.median_streak, 1.5);
+    assert_eq!(stats.average_streak, 1.5);
+}
+
+#[test]
+fn test_streaks() {
+    let today = date(2023, 2, 10);
+    let dates: BTreeSet<NaiveDate> = [(1, 30), (1, 31), (2, 1), (2, 2), (2, 5), (2, 9), (2, 10)]
+        .into_iter()
+        .map(|(m, d)| date(2023, m, d))
+        .collect();
+    assert_eq!(find_current_streak(&dates, today), 2);
+    assert_eq!(find_current_streak(&dates, date(2023, 2, 11)), 0);
+    assert_eq!(find_current_streak(&dates, date(2023, 2, 2)), 4);
+    assert_eq!(find_longest_streak(date(2023, 1, 1), &dates, today), 4);
+    // Only days since the start count
+    assert_eq!(find_longest_streak(date(2023, 2, 1), &dates, today), 2);
+    // Days after the current date don't count
+    assert_eq!(
+        find_longest_streak(date(2023, 1, 1), &dates, date(2023, 1, 31)),
+        2
+    );
+    // Starting date after the current date
+    assert_eq!(find_longest_streak(date(2023, 3, 1), &dates, today), 0);
+    let counts: BTreeMap<NaiveDate, u32> = dates.iter().map(|&date| (date, 1)).collect();
+    assert_eq!(find_current_streak(&counts, today), 2);
+    assert_eq!(find_longest_streak(date(2023, 1, 1), &counts, today), 4);
+}
+
+/// Compares against walking every day with a hash set lookup, like streaks used to be found.
+///
+/// Run with `cargo test --release -- --ignored --nocapture bench_streaks`
+#[test]
+#[ignore]
+fn bench_streaks() {
+    use std::{collections::HashSet, time::Instant};
+    const N_ACTIVITIES: usize = 100;
+    const N_DAYS: usize = 20 * 365;
+    let today = date(2023, 2, 10);
+    let start = today - chrono::Duration::days(N_DAYS as i64);
+    // Deterministic pseudo random history, about 70% of the days done
+    let mut seed: u32 = 12345;
+    let mut sets: Vec<BTreeSet<NaiveDate>> = Vec::new();
+    for _ in 0..N_ACTIVITIES {
+        let set = start
+            .iter_days()
+            .take(N_DAYS)
+            .filter(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) % 10 < 7
+            })
+            .collect();
+        sets.push(set);
+    }
+    let hash_sets: Vec<HashSet<NaiveDate>> = sets
+        .iter()
+        .map(|set| set.iter().copied().collect())
+        .collect();
+    let old_longest = |dates: &HashSet<NaiveDate>| {
+        let (mut longest, mut streak) = (0, 0);
+        for date in start.iter_days().take_while(|&date| date <= today) {
+            if dates.contains(&date) {
+                streak += 1;
+                longest = longest.max(streak);
+            } else {
+                streak = 0;
+            }
+        }
+        longest
+    };
+    let time = |name: &str, f: &mut dyn FnMut() -> u32| {
+        let now = Instant::now();
+        let mut result = 0;
+        for _ in 0..10 {
+            result = f();
+        }
+        println!("{name}: {:?} per run", now.elapsed() / 10);
+        result
+    };
+    // What happens after a click: rebuild the overview counts and find the longest streak
+    let old = time("day walk with full recount", &mut || {
+        let mut counts = std::collections::HashMap::new();
+        for set in &hash_sets {
+            for date in set {
+                *counts.entry(*date).or_insert(0u32) += 1;
+            }
+        }
+        let any: HashSet<NaiveDate> = counts.keys().copied().collect();
+        old_longest(&any) + old_longest(&hash_sets[0])
+    });
+    let mut counts = BTreeMap::new();
+    for set in &sets {
+        for date in set {
+            *counts.entry(*date).or_insert(0u32) += 1;
+        }
+    }
+    let new = time("ordered dates with incremental count", &mut || {
+        // A toggle only touches a single entry of the counts
+        *counts.entry(today).or_insert(0) += 1;
+        let count = counts.get_mut(&today).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&today);
+        }
+        find_longest_streak(start, &counts, today) + find_longest_streak(start, &sets[0], today)
+    });
+    assert_eq!(old, new);
+}
+
+#[test]
+fn test_gaps() {
+    let start = date(2023, 1, 1);
+    let today = date(2023, 1, 20);
+    let relapses: BTreeSet<_> = [date(2023, 1, 5), date(2023, 1, 6), date(2023, 1, 15)].into();
+    // The 16th to the 20th
+    assert_eq!(find_current_gap(&relapses, start, today), 5);
+    // The 7th to the 14th
+    assert_eq!(find_longest_gap(&relapses, start, today), 8);
+    assert_eq!(find_current_gap(&relapses, start, date(2023, 1, 15)), 0);
+    assert_eq!(find_longest_gap(&relapses, start, date(2023, 1, 6)), 4);
+    let clean = BTreeSet::new();
+    assert_eq!(find_current_gap(&clean, start, today), 20);
+    assert_eq!(find_longest_gap(&clean, start, today), 20);
+    assert_eq!(find_current_gap(&clean, today, start), 0);
+    assert_eq!(find_longest_gap(&clean, today, start), 0);
+}
+
+#[test]
+fn test_freezes() {
+    let days = |from: u32, to: u32| (from..=to).map(|day| date(2023, 1, day));
+    // 7 days earn a freeze, which covers the 8th
+    let dates: BTreeSet<_> = days(1, 7).chain(days(9, 10)).collect();
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 11));
+    assert_eq!(freezes.available, 0);
+    assert_eq!(freezes.covered, days(8, 8).collect());
+    let streak_dates = Union(&dates, &freezes.covered);
+    assert_eq!(find_current_streak(&streak_dates, date(2023, 1, 10)), 10);
+    // A gap longer than the freezes breaks the streak, without using any
+    let dates: BTreeSet<_> = days(1, 7).chain(days(10, 15)).collect();
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 16));
+    assert_eq!(freezes.available, 1);
+    assert!(freezes.covered.is_empty());
+    // Freezes are capped, and today isn't missed yet
+    let dates: BTreeSet<_> = days(1, 28).collect();
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 29));
+    assert_eq!(freezes.available, MAX_FREEZES);
+    assert!(freezes.covered.is_empty());
+    // A gap as long as the freezes uses all of them
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 31));
+    assert_eq!(freezes.available, 0);
+    assert_eq!(freezes.covered, days(29, 30).collect());
+    // A longer gap breaks the streak, and the freezes are kept for the next one
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 2, 1));
+    assert_eq!(freezes.available, MAX_FREEZES);
+    assert!(freezes.covered.is_empty());
+    // The kept freeze is used on a later gap
+    let dates: BTreeSet<_> = days(1, 7).chain(days(10, 16)).chain(days(18, 18)).collect();
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 18));
+    assert_eq!(freezes.available, 1);
+    assert_eq!(freezes.covered, days(17, 17).collect());
+    let dates: BTreeSet<_> = days(1, 28).chain(days(30, 30)).collect();
+    let freezes = compute_freezes(&dates, date(2023, 1, 1), date(2023, 1, 31));
+    assert_eq!(freezes.available, MAX_FREEZES - 1);
+    assert_eq!(freezes.covered, days(29, 29).collect());
+    // Nothing before the starting date
+    let freezes = compute_freezes(&dates, date(2023, 1, 5), date(2023, 1, 10));
+    assert_eq!(freezes.available, 0);
+}
+
+#[test]
+fn test_union() {
+    let days = |list: &[u32]| -> BTreeSet<_> { list.iter().map(|&d| date(2023, 1, d)).collect() };
+    let (a, b) = (days(&[1, 3, 4, 8]), days(&[2, 4, 6, 9]));
+    let union = Union(&a, &b);
+    let all = date(2023, 1, 1)..=date(2023, 1, 31);
+    let expected: Vec<_> = (&a | &b).into_iter().collect();
+    assert_eq!(union.dates_in(all.clone()).collect::<Vec<_>>(), expected);
+    let mut reversed: Vec<_> = union.dates_in(all.clone()).rev().collect();
+    reversed.reverse();
+    assert_eq!(reversed, expected);
+    // Taking from both ends meets in the middle
+    let mut iter = union.dates_in(all);
+    let mut ends = Vec::new();
+    while let (Some(first), last) = (iter.next(), iter.next_back()) {
+        ends.push(first);
+        ends.extend(last);
+    }
+    ends.sort();
+    assert_eq!(ends, expected);
+    assert!(union.has_date(date(2023, 1, 6)) && !union.has_date(date(2023, 1, 5)));
+}
+
+#[test]
+fn test_perfect_days() {
+    let start = date(2023, 2, 1);
+    let mut first = Activity::new_default(start);
+    let mut second = Activity::new_default(date(2023, 2, 3));
+    first.dates = [1, 2, 3, 4].into_iter().map(|d| date(2023, 2, d)).collect();
+    // Days before the starting date don't matter
+    second.dates = [1, 4, 5].into_iter().map(|d| date(2023, 2, d)).collect();
+    let activities = [&first, &second];
+    assert_eq!(
+        day_completion(&activities, date(2023, 2, 1)),
+        Rate { done: 1, total: 1 }
+    );
+    assert_eq!(
+        day_completion(&activities, date(2023, 2, 3)),
+        Rate { done: 1, total: 2 }
+    );
+    assert_eq!(
+        day_completion(&activities, date(2023, 2, 5)),
+        Rate { done: 1, total: 2 }
+    );
+    assert_eq!(
+        perfect_days(&activities),
+        [1, 2, 4].into_iter().map(|d| date(2023, 2, d)).collect()
+    );
+    assert!(perfect_days(&[]).is_empty());
+}
@@ -0,0 +1,445 @@
+//! Sharing the save file with other instances, or other devices through a synced folder
+
+use crate::user_data::{LoadError, MergeReport, UserData};
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How long to wait for another instance to finish writing
+const LOCK_TIMEOUT: Duration = Duration::from_secs(3);
+/// A lock older than this was left behind by a crashed instance
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+/// How often a running instance shows that it's still running
+const INSTANCE_HEARTBEAT: Duration = Duration::from_secs(10);
+
+/// What identifies a version of the save file, to notice when it's written by someone else
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    Some(Stamp {
+        modified: meta.modified().ok()?,
+        len: meta.len(),
+    })
+}
+
+/// Base path for the files of the save file at `save_path` that only concern this computer,
+/// like locks, in `local_dir`.
+///
+/// They are kept out of the folder of the save file, as it might be synced to other devices.
+pub fn local_files(local_dir: &Path, save_path: &Path) -> PathBuf {
+    // The same file for every way of writing its path, even if it doesn't exist yet
+    let dir = match save_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let absolute = match (fs::canonicalize(dir), save_path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => save_path.to_owned(),
+    };
+    // FNV-1a, which unlike the standard library hasher is the same in every build
+    let hash = absolute
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let stem = save_path.file_stem().unwrap_or_default().to_string_lossy();
+    local_dir.join(format!("{stem}-{hash:016x}"))
+}
+
+/// The file that `path` links to, so writing it keeps the link, like one to a synced folder
+fn resolve_links(path: &Path) -> PathBuf {
+    let mut path = path.to_owned();
+    // Limited, in case the links form a loop
+    for _ in 0..16 {
+        match fs::read_link(&path) {
+            Ok(target) => path = path.parent().unwrap_or(Path::new("")).join(target),
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+/// The save file, which might be changed by others while it's open.
+///
+/// Saving merges the changes others made since we last loaded or saved the file, instead
+/// of overwriting them, so days checked or unchecked and activities changed or deleted
+/// elsewhere are kept.
+pub struct SaveFile {
+    path: PathBuf,
+    /// Base path of the lock file, see `local_files`
+    local: PathBuf,
+    /// The version that was last loaded or saved by us
+    stamp: Option<Stamp>,
+    /// The data of that version, what both the file and the data in memory changed from
+    base: Option<UserData>,
+}
+
+impl SaveFile {
+    /// The save file at `path`, with its lock in `local_dir`
+    pub fn new(path: PathBuf, local_dir: &Path) -> Self {
+        Self {
+            local: local_files(local_dir, &path),
+            path,
+            stamp: None,
+            base: None,
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn load(&mut self) -> Result<UserData, LoadError> {
+        let stamp = stamp(&self.path);
+        let data = UserData::load(&self.path)?;
+        self.stamp = stamp;
+        self.base = Some(data.clone());
+        Ok(data)
+    }
+    /// Where `back_up` moves the file
+    pub fn backup_path(&self) -> PathBuf {
+        resolve_links(&self.path).with_extension("bak")
+    }
+    /// Moves the file out of the way, like a damaged one before it's replaced
+    pub fn back_up(&mut self) -> Result<(), Box<dyn Error>> {
+        let _lock = WriteLock::acquire(&self.local, LOCK_TIMEOUT)?;
+        fs::rename(resolve_links(&self.path), self.backup_path())?;
+        self.stamp = None;
+        self.base = None;
+        Ok(())
+    }
+    /// Whether the file was written by someone else since we last loaded or saved it
+    pub fn changed(&self) -> bool {
+        let current = stamp(&self.path);
+        current.is_some() && current != self.stamp
+    }
+    /// Merges the changes of others into `data`, if there are any
+    pub fn sync(&mut self, data: &mut UserData) -> Result<Option<MergeReport>, LoadError> {
+        if !self.changed() {
+            return Ok(None);
+        }
+        let stamp = stamp(&self.path);
+        let other = UserData::load(&self.path)?;
+        let report = match &self.base {
+            Some(base) => data.merge_since(base, other.clone()),
+            // The file appeared since, nothing can have been removed from it
+            None => data.merge(other.clone()),
+        };
+        self.stamp = stamp;
+        self.base = Some(other);
+        Ok(Some(report))
+    }
+    /// Merges the changes of others into `data`, then writes it
+    pub fn save(&mut self, data: &mut UserData) -> Result<Option<MergeReport>, Box<dyn Error>> {
+        let _lock = WriteLock::acquire(&self.local, LOCK_TIMEOUT)?;
+        let stamp = self.stamp;
+        let base = self.base.clone();
+        let report = self.sync(data)?;
+        if let Err(e) = self.write(data) {
+            // Merge the changes of others again next time, in case `data` is rolled back
+            self.stamp = stamp;
+            self.base = base;
+            return Err(e.into());
+        }
+        Ok(report)
+    }
+    /// Writes `data`, replacing whatever is in the file
+    pub fn overwrite(&mut self, data: &UserData) -> Result<(), Box<dyn Error>> {
+        let _lock = WriteLock::acquire(&self.local, LOCK_TIMEOUT)?;
+        self.write(data)?;
+        Ok(())
+    }
+    /// Writes to a temporary file first, so others never read a half written file.
+    ///
+    /// That one has to be next to the save file for the rename to be atomic.
+    fn write(&mut self, data: &UserData) -> io::Result<()> {
+        let path = resolve_links(&self.path);
+        let tmp_path = path.with_extension("tmp");
+        let mut f = BufWriter::new(File::create(&tmp_path)?);
+        data.write_to(&mut f)?;
+        f.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        self.stamp = stamp(&self.path);
+        self.base = Some(data.clone());
+        Ok(())
+    }
+}
+
+/// Keeps other instances on this computer from writing the save file, until dropped
+struct WriteLock {
+    path: PathBuf,
+}
+
+impl WriteLock {
+    /// Takes the lock of the save file with the `local` files, see `local_files`
+    fn acquire(local: &Path, timeout: Duration) -> io::Result<Self> {
+        let path = local.with_extension("lock");
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut f) => {
+                    // Only informative, for whoever finds a stale lock
+                    let _ = write!(f, "{}", std::process::id());
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path, STALE_LOCK_AGE) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() >= timeout {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            format!(
+                                "The save file is locked by another instance. \
+                                 Delete {} if there is none.",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn is_stale(lock_path: &Path, max_age: Duration) -> bool {
+    let age = fs::metadata(lock_path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok());
+    matches!(age, Some(age) if age >= max_age)
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Marks the save file as being used by a running instance, until dropped.
+///
+/// The lock is renewed by a background thread, so the lock of a crashed instance expires,
+/// but not the one of an instance that is busy or showing a dialog.
+pub struct InstanceLock {
+    path: PathBuf,
+    raise_path: PathBuf,
+    /// Dropping the sender stops the heartbeat thread
+    heartbeat: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl InstanceLock {
+    /// Takes the lock for the save file with the `local_files` base `local`, or returns
+    /// `None` if another instance is running with it on this computer
+    pub fn acquire(local: &Path) -> io::Result<Option<Self>> {
+        let path = local.with_extension("instance");
+        if is_stale(&path, INSTANCE_HEARTBEAT * 3) {
+            let _ = fs::remove_file(&path);
+        }
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut f) => {
+                write!(f, "{}", std::process::id())?;
+                let (stop, stopped) = mpsc::channel();
+                let heartbeat_path = path.clone();
+                let thread = thread::spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) =
+                        stopped.recv_timeout(INSTANCE_HEARTBEAT)
+                    {
+                        let _ = heartbeat(&heartbeat_path);
+                    }
+                });
+                Ok(Some(Self {
+                    path,
+                    raise_path: local.with_extension("raise"),
+                    heartbeat: Some((stop, thread)),
+                }))
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// Whether another instance asked this one to come to the front since the last call
+    pub fn take_raise_request(&self) -> bool {
+        fs::remove_file(&self.raise_path).is_ok()
+    }
+}
+
+/// Shows that this instance is still running
+fn heartbeat(path: &Path) -> io::Result<()> {
+    write!(File::create(path)?, "{}", std::process::id())
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Wait for the thread, so it doesn't recreate the file after it's removed
+        if let Some((stop, thread)) = self.heartbeat.take() {
+            drop(stop);
+            let _ = thread.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Asks the instance that holds the `InstanceLock` of `local` to come to the front
+pub fn request_raise(local: &Path) -> io::Result<()> {
+    File::create(local.with_extension("raise"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("calen-do-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_save_merges_changes_of_others() {
+    use chrono::NaiveDate;
+    let dir = test_dir("sync");
+    let path = dir.join("calen-do.dat");
+    let date = |day| NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+    let mut first = SaveFile::new(path.clone(), &dir);
+    let mut first_data = UserData::new_default(date(1));
+    assert!(first.save(&mut first_data).unwrap().is_none());
+    let mut second = SaveFile::new(path.clone(), &dir);
+    let mut second_data = second.load().unwrap();
+    assert!(!second.changed());
+    second_data.activities[0].dates.insert(date(2));
+    second.save(&mut second_data).unwrap();
+    assert!(first.changed());
+    first_data.activities[0].dates.insert(date(3));
+    let report = first.save(&mut first_data).unwrap().unwrap();
+    assert_eq!(report.new_days, 1);
+    assert!(!first.changed());
+    let report = second.sync(&mut second_data).unwrap().unwrap();
+    assert_eq!(report.new_days, 1);
+    assert_eq!(second_data.activities[0].dates.len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_save_applies_removals_of_others() {
+    use chrono::NaiveDate;
+    let dir = test_dir("sync-removals");
+    let path = dir.join("calen-do.dat");
+    let date = |day| NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+    let mut first = SaveFile::new(path.clone(), &dir);
+    let mut first_data = UserData::new_default(date(1));
+    first_data.insert_default_activity(1, date(1));
+    first_data.activities[0].dates.extend([date(1), date(2)]);
+    first.save(&mut first_data).unwrap();
+    let mut second = SaveFile::new(path.clone(), &dir);
+    let mut second_data = second.load().unwrap();
+    // Uncheck, rename and delete elsewhere
+    second_data.activities[0].dates.remove(&date(1));
+    second_data.activities[0].name = "Run".to_owned();
+    second_data.activities.remove(1);
+    second.save(&mut second_data).unwrap();
+    // Meanwhile, check another day here
+    first_data.activities[0].dates.insert(date(3));
+    let report = first.save(&mut first_data).unwrap().unwrap();
+    assert_eq!(report.removed_days, 1);
+    assert_eq!(report.removed.len(), 1);
+    assert!(report.conflicts.is_empty());
+    assert_eq!(first_data.activities.len(), 1);
+    let run = &first_data.activities[0];
+    assert_eq!(run.name, "Run");
+    assert_eq!(
+        run.dates.iter().copied().collect::<Vec<_>>(),
+        [date(2), date(3)]
+    );
+    // Nothing comes back when the other instance syncs again
+    let report = second.sync(&mut second_data).unwrap().unwrap();
+    assert_eq!(report.new_days, 1);
+    assert_eq!(report.removed_days, 0);
+    assert!(report.added.is_empty());
+    assert_eq!(second_data.activities.len(), 1);
+    assert_eq!(second_data.activities[0].dates.len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_save_through_link() {
+    let dir = test_dir("link");
+    let synced = dir.join("synced");
+    let local = dir.join("local");
+    fs::create_dir(&synced).unwrap();
+    fs::create_dir(&local).unwrap();
+    let link = dir.join("calen-do.dat");
+    std::os::unix::fs::symlink(synced.join("calen-do.dat"), &link).unwrap();
+    let mut save_file = SaveFile::new(link.clone(), &local);
+    let mut data = UserData::new_default(crate::date_util::today());
+    save_file.save(&mut data).unwrap();
+    save_file.save(&mut data).unwrap();
+    // The link is kept, and only the save file ends up in the synced folder
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert!(UserData::load(&synced.join("calen-do.dat")).is_ok());
+    assert_eq!(fs::read_dir(&synced).unwrap().count(), 1);
+    assert_eq!(
+        local_files(&local, &link),
+        local_files(&local, &synced.join("..").join("calen-do.dat"))
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_back_up() {
+    let dir = test_dir("backup");
+    let path = dir.join("calen-do.dat");
+    fs::write(&path, b"damaged").unwrap();
+    let mut save_file = SaveFile::new(path.clone(), &dir);
+    assert!(save_file.load().is_err());
+    save_file.back_up().unwrap();
+    assert_eq!(fs::read(save_file.backup_path()).unwrap(), b"damaged");
+    let mut data = UserData::new_default(crate::date_util::today());
+    assert!(save_file.save(&mut data).unwrap().is_none());
+    assert!(UserData::load(&path).is_ok());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_write_lock() {
+    let dir = test_dir("lock");
+    let path = dir.join("calen-do.dat");
+    let lock = WriteLock::acquire(&path, Duration::ZERO).unwrap();
+    assert!(WriteLock::acquire(&path, Duration::from_millis(100)).is_err());
+    drop(lock);
+    assert!(WriteLock::acquire(&path, Duration::ZERO).is_ok());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_instance_lock() {
+    let dir = test_dir("instance");
+    let path = local_files(&dir, &dir.join("calen-do.dat"));
+    let lock = InstanceLock::acquire(&path).unwrap().unwrap();
+    assert!(InstanceLock::acquire(&path).unwrap().is_none());
+    // Other save files have their own instance
+    let other = local_files(&dir, &dir.join("other").join("calen-do.dat"));
+    assert!(InstanceLock::acquire(&other).unwrap().is_some());
+    assert!(!lock.take_raise_request());
+    request_raise(&path).unwrap();
+    assert!(lock.take_raise_request());
+    assert!(!lock.take_raise_request());
+    drop(lock);
+    assert!(InstanceLock::acquire(&path).unwrap().is_some());
+    fs::remove_dir_all(dir).unwrap();
+}
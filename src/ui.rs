@@ -1,136 +1,476 @@
-use crate::{
-    date_util::{self, DAYS_PER_WEEK},
-    user_data::UserData,
-};
+use crate::config::Config;
 use button::Button;
+use calen_do::{
+    date_util::{self, WeekStart, DAYS_PER_WEEK},
+    edit_policy::Permission,
+    goals::{self, Goal},
+    stats::{self, find_current_streak, find_longest_streak, HasDate, Stats},
+    sync::{InstanceLock, SaveFile},
+    user_data::{self, UserData, MAX_NAME_LEN},
+};
 use chrono::prelude::*;
+pub use keys::KeyCombo;
 use layout::*;
+pub use names::Language;
+use names::Names;
 use sfml::{graphics::*, system::Vector2, window::*};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::BTreeMap,
     error::Error,
+    time::{Duration, Instant},
 };
+use text_field::TextField;
 
 mod button;
 mod color;
+mod keys;
 mod layout;
 mod names;
 mod render;
+mod text_field;
 
-type NActivitiesCache = HashMap<NaiveDate, u8>;
-type ActivityIdx = u8;
+const DATE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const CELEBRATION_DURATION: Duration = Duration::from_secs(3);
+/// How often to look for changes of the save file by other instances or devices
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+type NActivitiesCache = BTreeMap<NaiveDate, u32>;
+type ActivityIdx = usize;
 
 struct UiState {
     side_ui: SideUi,
     imode: InteractMode,
     current_activity: ActivityIdx,
+    /// Group that navigation and the overview are limited to, all activities if `None`
+    group: Option<String>,
     overview: bool,
+    /// Whether overview streaks need every habit done, instead of any
+    all_habits_streak: bool,
     n_activities_cache: NActivitiesCache,
     edit_mode: bool,
     day_boxes: Vec<DayBox>,
     longest_streak: u32,
     current_streak: u32,
+    /// Whether the statistics are shown instead of the calendar
+    stats_view: bool,
+    stats: Stats,
+    /// Whether the badge gallery is shown instead of the calendar
+    achievements_view: bool,
+    rename_field: TextField,
+    /// Banner shown after reaching a goal
+    celebration: Option<Celebration>,
+    week_start: WeekStart,
+    names: &'static Names,
 }
 
 impl UiState {
-    fn new(current_date: NaiveDate) -> Self {
+    fn new(current_date: NaiveDate, week_start: WeekStart, language: Language) -> Self {
         Self {
             side_ui: SideUi::new(),
             imode: InteractMode::Default,
             current_activity: 0,
+            group: None,
             overview: false,
-            n_activities_cache: HashMap::new(),
+            all_habits_streak: false,
+            n_activities_cache: BTreeMap::new(),
             edit_mode: false,
-            day_boxes: gen_day_boxes(current_date),
+            day_boxes: gen_day_boxes(current_date, week_start),
             current_streak: 0,
             longest_streak: 0,
+            stats_view: false,
+            stats: Stats::default(),
+            achievements_view: false,
+            rename_field: TextField::new("", MAX_NAME_LEN),
+            celebration: None,
+            week_start,
+            names: language.names(),
         }
     }
-    fn update_streaks(&mut self, user_data: &mut UserData, current_date: NaiveDate) {
-        if self.overview {
-            self.current_streak = find_current_streak(&self.n_activities_cache, current_date);
-            let earliest_start = user_data
-                .activities
-                .iter()
-                .min_by_key(|a| a.starting_date)
-                .unwrap()
-                .starting_date;
-            self.longest_streak =
-                find_longest_streak(earliest_start, &self.n_activities_cache, current_date)
-        } else {
-            let activity = &user_data.activities[self.current_activity as usize];
-            self.current_streak = find_current_streak(&activity.dates, current_date);
-            self.longest_streak =
-                find_longest_streak(activity.starting_date, &activity.dates, current_date);
+    fn press_button(&mut self, id: button::Id, user_data: &mut UserData, current_date: NaiveDate) {
+        use button::Id::*;
+        match id {
+            CurrentActivity => {
+                self.rename_field = TextField::new(
+                    &user_data.activities[self.current_activity].name,
+                    MAX_NAME_LEN,
+                );
+                self.imode = InteractMode::ActivityRename;
+            }
+            PrevActivity => {
+                let group = self.group.as_deref();
+                if let Some(idx) = (0..self.current_activity)
+                    .rev()
+                    .find(|&i| user_data.activities[i].in_group(group))
+                {
+                    self.current_activity = idx;
+                }
+            }
+            AddActivity => {
+                user_data.insert_default_activity(self.current_activity + 1, current_date);
+                self.current_activity += 1;
+                // New activities go to the group that is being browsed
+                user_data.activities[self.current_activity].group =
+                    self.group.clone().unwrap_or_default();
+            }
+            RemActivity => {
+                if user_data.activities.len() > 1 {
+                    let removed = user_data.activities.remove(self.current_activity);
+                    if removed.counts_in_overview(self.group.as_deref()) {
+                        for date in &removed.dates {
+                            uncount_date(&mut self.n_activities_cache, *date);
+                        }
+                    }
+                    if self.current_activity > 0 {
+                        self.current_activity -= 1;
+                    }
+                    self.select_in_group(user_data);
+                }
+            }
+            NextActivity => {
+                let group = self.group.as_deref();
+                if let Some(idx) = (self.current_activity + 1..user_data.activities.len())
+                    .find(|&i| user_data.activities[i].in_group(group))
+                {
+                    self.current_activity = idx;
+                }
+            }
+            Overview => self.overview = !self.overview,
+            SetStartingDate => self.imode = InteractMode::StartingDateSelect,
+            EditMode => self.edit_mode = !self.edit_mode,
+            Statistics => {
+                self.stats_view = !self.stats_view;
+                self.achievements_view = false;
+            }
+            Achievements => {
+                self.achievements_view = !self.achievements_view;
+                self.stats_view = false;
+            }
+            StreakMode => self.all_habits_streak = !self.all_habits_streak,
+            InOverview => {
+                let activity = &mut user_data.activities[self.current_activity];
+                activity.in_overview = !activity.in_overview;
+                self.recompute_cache(user_data);
+            }
+            Polarity => {
+                let activity = &mut user_data.activities[self.current_activity];
+                activity.polarity = if activity.is_negative() {
+                    user_data::Polarity::Positive
+                } else {
+                    user_data::Polarity::Negative
+                };
+                activity.update_freezes(current_date);
+                self.recompute_cache(user_data);
+            }
+            GroupFilter => {
+                // Cycle through all activities, then each group in order
+                let groups = user_data.groups();
+                let next = match &self.group {
+                    None => groups.iter().next(),
+                    Some(current) => groups.iter().find(|&&group| group > current.as_str()),
+                };
+                self.group = next.map(|&group| group.to_owned());
+                self.select_in_group(user_data);
+                self.recompute_cache(user_data);
+            }
+            ActivityGroup => {
+                self.rename_field = TextField::new(
+                    &user_data.activities[self.current_activity].group,
+                    MAX_NAME_LEN,
+                );
+                self.imode = InteractMode::GroupRename;
+            }
+            Goals => {
+                self.rename_field = TextField::new(
+                    &goals::format_list(&user_data.activities[self.current_activity].goals),
+                    MAX_NAME_LEN,
+                );
+                self.imode = InteractMode::GoalsEdit;
+            }
+            MergeSaveFile => self.merge_save_file(user_data, current_date),
         }
     }
-}
-
-trait HasDate {
-    fn has_date(&self, date: NaiveDate) -> bool;
-}
-
-impl HasDate for NActivitiesCache {
-    fn has_date(&self, date: NaiveDate) -> bool {
-        self.contains_key(&date)
+    /// Let the user pick a save file, like the one of another computer, and merge it
+    fn merge_save_file(&mut self, user_data: &mut UserData, current_date: NaiveDate) {
+        let path = match rfd::FileDialog::new()
+            .set_title("Merge save file")
+            .add_filter("Calen-Do save file", &["dat"])
+            .pick_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        match UserData::load(&path) {
+            Ok(other) => {
+                let report = user_data.merge(other);
+                for activity in &mut user_data.activities {
+                    activity.update_freezes(current_date);
+                }
+                self.recompute_cache(user_data);
+                show_info("Merged save file", &report.to_string());
+            }
+            Err(e) => show_error(
+                "Can't merge save file",
+                &format!("Can't load {}: {e}", path.display()),
+            ),
+        }
     }
-}
-
-impl HasDate for HashSet<NaiveDate> {
-    fn has_date(&self, date: NaiveDate) -> bool {
-        self.contains(&date)
+    /// Start typing a search for an activity, in place of the activity name
+    fn start_search(&mut self) {
+        self.rename_field = TextField::new("", MAX_NAME_LEN);
+        self.imode = InteractMode::ActivitySearch;
     }
-}
-
-fn find_current_streak<T: HasDate>(dates: &T, current_date: NaiveDate) -> u32 {
-    let mut date_counter = current_date;
-    // Count down from current date, until we find an unfilled day
-    for n in 0.. {
-        if !dates.has_date(date_counter) {
-            return n;
+    /// Select the first or the last activity of the current group
+    fn select_end(&mut self, user_data: &UserData, last: bool) {
+        let group = self.group.as_deref();
+        let mut in_group =
+            (0..user_data.activities.len()).filter(|&i| user_data.activities[i].in_group(group));
+        let idx = if last {
+            in_group.next_back()
+        } else {
+            in_group.next()
+        };
+        if let Some(idx) = idx {
+            self.current_activity = idx;
         }
-        date_counter = date_counter.pred_opt().unwrap();
     }
-    0
-}
-
-fn find_longest_streak<T: HasDate>(start: NaiveDate, dates: &T, current_date: NaiveDate) -> u32 {
-    let mut date_counter = start;
-    let mut longest = 0;
-    let mut streak = 0;
-    loop {
-        if dates.has_date(date_counter) {
-            streak += 1;
+    fn end_rename(&mut self, user_data: &mut UserData, commit: bool) {
+        if matches!(self.imode, InteractMode::ActivitySearch) {
+            self.imode = InteractMode::Default;
+            if commit {
+                self.search(user_data);
+            }
+            return;
+        }
+        let text = if commit {
+            self.rename_field.text()
         } else {
-            if streak > longest {
-                longest = streak;
+            self.rename_field.original()
+        };
+        let activity = &mut user_data.activities[self.current_activity];
+        if matches!(self.imode, InteractMode::GoalsEdit) {
+            match Goal::parse_list(text) {
+                Ok(goals) => activity.goals = goals,
+                Err(e) => show_error("Invalid goals", &e),
             }
-            streak = 0;
+        } else if matches!(self.imode, InteractMode::GroupRename) {
+            activity.group = text.trim().to_owned();
+            // Follow the activity to its new group, so it doesn't disappear from navigation
+            if self.group.is_some() {
+                self.group = Some(activity.group.clone()).filter(|group| !group.is_empty());
+            }
+            self.recompute_cache(user_data);
+        } else {
+            activity.name = text.to_owned();
+        }
+        self.imode = InteractMode::Default;
+    }
+    /// Select the next activity matching the search text, showing all activities if it's
+    /// in another group
+    fn search(&mut self, user_data: &UserData) {
+        let query = self.rename_field.text();
+        if query.is_empty() {
+            return;
         }
-        if date_counter == current_date {
-            if streak > longest {
-                longest = streak;
+        match find_activity(&user_data.activities, query, self.current_activity) {
+            Some(idx) => {
+                self.current_activity = idx;
+                if !user_data.activities[idx].in_group(self.group.as_deref()) {
+                    self.group = None;
+                    self.recompute_cache(user_data);
+                }
             }
-            break;
+            None => show_error("Search", &format!("No activity matches \"{query}\"")),
+        }
+    }
+    /// Make sure the current activity is in the current group, or show all activities
+    /// if the group has none left
+    fn select_in_group(&mut self, user_data: &UserData) {
+        let group = self.group.as_deref();
+        if user_data.activities[self.current_activity].in_group(group) {
+            return;
+        }
+        match user_data.activities.iter().position(|a| a.in_group(group)) {
+            Some(idx) => self.current_activity = idx,
+            None => {
+                self.group = None;
+                self.recompute_cache(user_data);
+            }
+        }
+    }
+    fn recompute_cache(&mut self, user_data: &UserData) {
+        compute_n_activities_cache(
+            &mut self.n_activities_cache,
+            user_data,
+            self.group.as_deref(),
+        );
+    }
+    /// Update everything that depends on the activities, after changes from another
+    /// instance or device were merged
+    fn merged_changes(&mut self, user_data: &mut UserData, current_date: NaiveDate) {
+        for activity in &mut user_data.activities {
+            activity.update_freezes(current_date);
+        }
+        user_data.achievements.update(&user_data.activities);
+        // The current activity might have been deleted elsewhere
+        self.current_activity = self.current_activity.min(user_data.activities.len() - 1);
+        self.select_in_group(user_data);
+        self.recompute_cache(user_data);
+        self.update_streaks(user_data, current_date);
+        self.celebration = Some(Celebration {
+            message: "Synced changes from elsewhere".to_owned(),
+            start: Instant::now(),
+        });
+    }
+    /// Recompute everything that depends on the current date, after the day changed
+    fn change_date(&mut self, user_data: &mut UserData, new_date: NaiveDate) {
+        self.day_boxes = gen_day_boxes(new_date, self.week_start);
+        for activity in &mut user_data.activities {
+            activity.update_freezes(new_date);
+        }
+        self.recompute_cache(user_data);
+        self.update_streaks(user_data, new_date);
+    }
+    fn update_streaks(&mut self, user_data: &mut UserData, current_date: NaiveDate) {
+        let (current, longest, stats) = if self.overview {
+            let group = self.group.as_deref();
+            let earliest_start = user_data.overview_start(group).unwrap_or(current_date);
+            if self.all_habits_streak {
+                let activities: Vec<_> = user_data.overview_activities(group).collect();
+                let perfect_days = stats::perfect_days(&activities);
+                streaks(
+                    &perfect_days,
+                    &perfect_days,
+                    earliest_start,
+                    current_date,
+                    self.stats_view,
+                )
+            } else {
+                streaks(
+                    &self.n_activities_cache,
+                    &self.n_activities_cache,
+                    earliest_start,
+                    current_date,
+                    self.stats_view,
+                )
+            }
+        } else if user_data.activities[self.current_activity].is_negative() {
+            // Streaks of negative habits are the clean days between relapses
+            let activity = &user_data.activities[self.current_activity];
+            let start = activity.starting_date;
+            (
+                stats::find_current_gap(&activity.dates, start, current_date),
+                stats::find_longest_gap(&activity.dates, start, current_date),
+                self.stats_view.then(|| {
+                    stats::compute(&activity.clean_days(current_date), start, current_date)
+                }),
+            )
+        } else {
+            // Days covered by a freeze keep the streak going, but aren't done
+            let activity = &user_data.activities[self.current_activity];
+            streaks(
+                &activity.streak_dates(),
+                &activity.dates,
+                activity.starting_date,
+                current_date,
+                self.stats_view,
+            )
+        };
+        self.current_streak = current;
+        self.longest_streak = longest;
+        if let Some(stats) = stats {
+            self.stats = stats;
         }
-        date_counter = date_counter.succ_opt().unwrap();
     }
-    longest
 }
 
-pub fn run(current_date: NaiveDate, user_data: &mut UserData) -> Result<(), Box<dyn Error>> {
+/// Current and longest streak of `streak_dates`, and the statistics of `dates`
+/// if `with_stats` is set.
+///
+/// Unlike streaks, statistics visit every day, so they are only computed when shown.
+fn streaks<T: HasDate, U: HasDate>(
+    streak_dates: &T,
+    dates: &U,
+    start: NaiveDate,
+    current_date: NaiveDate,
+    with_stats: bool,
+) -> (u32, u32, Option<Stats>) {
+    (
+        find_current_streak(streak_dates, current_date),
+        find_longest_streak(start, streak_dates, current_date),
+        with_stats.then(|| stats::compute(dates, start, current_date)),
+    )
+}
+
+/// Runs the calendar window until it's closed.
+///
+/// Without an `instance` lock, another instance is running, and the data can only be looked
+/// at, as nothing would be saved.
+pub fn run(
+    mut current_date: NaiveDate,
+    user_data: &mut UserData,
+    config: &Config,
+    week_start: WeekStart,
+    language: Language,
+    save_file: &mut SaveFile,
+    instance: Option<&InstanceLock>,
+) -> Result<(), Box<dyn Error>> {
     let mut t: f32 = 0.;
     let res = render::Resources::load()?;
-    let mut render_ctx = render::RenderContext::with_resources(&res);
+    let mut render_ctx = render::RenderContext::new(&res, &config.theme);
     let mut bg_shader =
         Shader::from_memory(include_str!("../bgshader.glsl"), ShaderType::Fragment)?;
     bg_shader.set_uniform_vec2("res", Vector2::new(f32::from(RES.0), f32::from(RES.1)));
     let bg_rect = RectangleShape::with_size(Vector2::new(f32::from(RES.0), f32::from(RES.1)));
-    let mut ui_state = UiState::new(current_date);
+    let mut ui_state = UiState::new(current_date, week_start, language);
+    for activity in &mut user_data.activities {
+        activity.update_freezes(current_date);
+    }
+    ui_state.recompute_cache(user_data);
     ui_state.update_streaks(user_data, current_date);
+    // Badges of days checked before achievements existed are recorded without celebration
+    user_data.achievements.update(&user_data.activities);
+    let edit_policy = config.edit_policy();
+    let autosave_interval = Duration::from_secs(config.autosave_interval_secs);
+    let mut last_save = Instant::now();
+    let mut last_date_check = Instant::now();
+    let mut last_sync_check = Instant::now();
+    let read_only = instance.is_none();
+    if read_only {
+        render_ctx.rw.set_title("Calen-Do! (read-only)");
+    }
 
     while render_ctx.rw.is_open() {
+        // The app can be left open past midnight
+        if last_date_check.elapsed() >= DATE_CHECK_INTERVAL {
+            last_date_check = Instant::now();
+            let today = date_util::today();
+            if today != current_date {
+                current_date = today;
+                ui_state.change_date(user_data, current_date);
+            }
+        }
         while let Some(ev) = render_ctx.rw.poll_event() {
+            // Clicking anywhere outside the edited field commits the rename, and nothing else
+            if let Event::MouseButtonPressed { x, y, .. } = ev {
+                let field_button = match ui_state.imode {
+                    InteractMode::ActivityRename | InteractMode::ActivitySearch => Some(0),
+                    InteractMode::GroupRename => Some(12),
+                    InteractMode::GoalsEdit => Some(13),
+                    _ => None,
+                };
+                if let Some(n) = field_button {
+                    if !ui_state.side_ui.buttons[n]
+                        .rect
+                        .contains2(x as f32, y as f32)
+                    {
+                        ui_state.end_rename(user_data, true);
+                        ui_state.update_streaks(user_data, current_date);
+                        // The click only leaves the field, it doesn't toggle a day or
+                        // press a button too
+                        continue;
+                    }
+                }
+            }
             match ev {
                 Event::Closed => render_ctx.rw.close(),
                 Event::MouseButtonPressed {
@@ -141,70 +481,52 @@ pub fn run(current_date: NaiveDate, user_data: &mut UserData) -> Result<(), Box<
                     InteractMode::Default => {
                         for day_box in &ui_state.day_boxes {
                             let box_date = day_box.date;
-                            if (ui_state.edit_mode
-                                || (box_date == current_date
-                                    || box_date == current_date.pred_opt().unwrap()))
-                                && Rect::new(
+                            if ui_state.stats_view
+                                || ui_state.achievements_view
+                                || !Rect::new(
                                     day_box.x,
                                     day_box.y,
                                     u16::from(DAYBOX_SIZE),
                                     u16::from(DAYBOX_SIZE),
                                 )
                                 .contains2(x as u16, y as u16)
-                                && !user_data.activities[ui_state.current_activity as usize]
-                                    .dates
-                                    .insert(box_date)
                             {
-                                user_data.activities[ui_state.current_activity as usize]
-                                    .dates
-                                    .remove(&box_date);
+                                continue;
                             }
-                        }
-                        for button in &ui_state.side_ui.buttons {
-                            if !button.hidden && button.rect.contains2(x as f32, y as f32) {
-                                use button::Id::*;
-                                match button.id {
-                                    CurrentActivity => {
-                                        ui_state.imode = InteractMode::ActivityRename
-                                    }
-                                    PrevActivity => {
-                                        if ui_state.current_activity > 0 {
-                                            ui_state.current_activity -= 1;
-                                        }
-                                    }
-                                    AddActivity => {
-                                        user_data.insert_default_activity(
-                                            ui_state.current_activity as usize + 1,
-                                            current_date,
-                                        );
-                                        ui_state.current_activity += 1;
-                                    }
-                                    RemActivity => {
-                                        if user_data.activities.len() > 1 {
-                                            user_data
-                                                .activities
-                                                .remove(ui_state.current_activity as usize);
-                                            if ui_state.current_activity > 0 {
-                                                ui_state.current_activity -= 1;
-                                            }
-                                        }
-                                    }
-                                    NextActivity => {
-                                        if (ui_state.current_activity as usize)
-                                            < user_data.activities.len() - 1
-                                        {
-                                            ui_state.current_activity += 1;
-                                        }
-                                    }
-                                    Overview => ui_state.overview = !ui_state.overview,
-                                    SetStartingDate => {
-                                        ui_state.imode = InteractMode::StartingDateSelect
-                                    }
-                                    EditMode => ui_state.edit_mode = !ui_state.edit_mode,
+                            let allowed = !read_only
+                                && match edit_policy.check(
+                                    box_date,
+                                    current_date,
+                                    ui_state.edit_mode,
+                                ) {
+                                    Permission::Allowed => true,
+                                    Permission::NeedsConfirmation => confirm_old_edit(box_date),
+                                    Permission::Denied => false,
+                                };
+                            if allowed {
+                                let celebration = toggle_date(
+                                    &mut ui_state.n_activities_cache,
+                                    user_data,
+                                    ui_state.current_activity,
+                                    ui_state.group.as_deref(),
+                                    box_date,
+                                    current_date,
+                                );
+                                if celebration.is_some() {
+                                    ui_state.celebration = celebration;
                                 }
                             }
                         }
-                        compute_n_activities_cache(&mut ui_state.n_activities_cache, user_data);
+                        let clicked = ui_state
+                            .side_ui
+                            .buttons
+                            .iter()
+                            .find(|b| !b.hidden && b.rect.contains2(x as f32, y as f32))
+                            .map(|b| b.id)
+                            .filter(|id| !read_only || !id.edits_data());
+                        if let Some(id) = clicked {
+                            ui_state.press_button(id, user_data, current_date);
+                        }
                         ui_state.update_streaks(user_data, current_date);
                     }
                     InteractMode::StartingDateSelect => {
@@ -217,8 +539,9 @@ pub fn run(current_date: NaiveDate, user_data: &mut UserData) -> Result<(), Box<
                             )
                             .contains2(x as u16, y as u16)
                             {
-                                user_data.activities[ui_state.current_activity as usize]
-                                    .starting_date = day_box.date;
+                                let activity = &mut user_data.activities[ui_state.current_activity];
+                                activity.starting_date = day_box.date;
+                                activity.update_freezes(current_date);
                                 ui_state.imode = InteractMode::Default;
                             }
                         }
@@ -228,22 +551,78 @@ pub fn run(current_date: NaiveDate, user_data: &mut UserData) -> Result<(), Box<
                             }
                         }
                     }
-                    InteractMode::ActivityRename => {}
+                    InteractMode::ActivityRename
+                    | InteractMode::GroupRename
+                    | InteractMode::GoalsEdit
+                    | InteractMode::ActivitySearch => {}
                 },
-                Event::TextEntered { unicode } => {
-                    if matches!(ui_state.imode, InteractMode::ActivityRename) {
-                        if unicode == 0x8 as char {
-                            user_data.activities[ui_state.current_activity as usize]
-                                .name
-                                .pop();
-                        } else if unicode == 0xD as char {
-                            ui_state.imode = InteractMode::Default;
-                        } else {
-                            user_data.activities[ui_state.current_activity as usize]
-                                .name
-                                .push(unicode);
+                Event::TextEntered { .. } | Event::KeyPressed { .. }
+                    if matches!(
+                        ui_state.imode,
+                        InteractMode::ActivityRename
+                            | InteractMode::GroupRename
+                            | InteractMode::GoalsEdit
+                            | InteractMode::ActivitySearch
+                    ) =>
+                {
+                    match ui_state.rename_field.handle_event(&ev) {
+                        text_field::Outcome::Editing => {}
+                        text_field::Outcome::Commit => ui_state.end_rename(user_data, true),
+                        text_field::Outcome::Cancel => ui_state.end_rename(user_data, false),
+                    }
+                }
+                Event::KeyPressed { .. } if matches!(ui_state.imode, InteractMode::Default) => {
+                    let keys = &config.keys;
+                    if keys.quit.matches(&ev) {
+                        render_ctx.rw.close();
+                    } else if keys.toggle_today.matches(&ev) {
+                        if !ui_state.overview && !read_only {
+                            let celebration = toggle_date(
+                                &mut ui_state.n_activities_cache,
+                                user_data,
+                                ui_state.current_activity,
+                                ui_state.group.as_deref(),
+                                current_date,
+                                current_date,
+                            );
+                            if celebration.is_some() {
+                                ui_state.celebration = celebration;
+                            }
+                        }
+                    } else if !ui_state.overview && keys.search_activity.matches(&ev) {
+                        ui_state.start_search();
+                    } else if !ui_state.overview && keys.first_activity.matches(&ev) {
+                        ui_state.select_end(user_data, false);
+                    } else if !ui_state.overview && keys.last_activity.matches(&ev) {
+                        ui_state.select_end(user_data, true);
+                    } else {
+                        use button::Id::*;
+                        let bindings = [
+                            (keys.prev_activity, PrevActivity),
+                            (keys.next_activity, NextActivity),
+                            (keys.overview, Overview),
+                            (keys.edit_mode, EditMode),
+                            (keys.rename_activity, CurrentActivity),
+                            (keys.next_group, GroupFilter),
+                        ];
+                        // Keys act like clicking the corresponding button, if it's visible
+                        let pressed = bindings
+                            .iter()
+                            .find(|(key, _)| key.matches(&ev))
+                            .map(|&(_, id)| id)
+                            .filter(|&id| {
+                                ui_state
+                                    .side_ui
+                                    .buttons
+                                    .iter()
+                                    .any(|b| b.id == id && !b.hidden)
+                            })
+                            .filter(|id| !read_only || !id.edits_data());
+                        if let Some(id) = pressed {
+                            ui_state.press_button(id, user_data, current_date);
                         }
                     }
+                    ui_state.update_streaks(user_data, current_date);
                 }
                 _ => {}
             }
@@ -251,41 +630,184 @@ pub fn run(current_date: NaiveDate, user_data: &mut UserData) -> Result<(), Box<
             for n in 0..5 {
                 ui_state.side_ui.buttons[n].hidden = ui_state.overview;
             }
-            for n in 6..ui_state.side_ui.buttons.len() {
-                ui_state.side_ui.buttons[n].hidden = ui_state.overview;
+            for n in 6..8 {
+                ui_state.side_ui.buttons[n].hidden =
+                    ui_state.overview || ui_state.stats_view || ui_state.achievements_view;
             }
+            ui_state.side_ui.buttons[9].hidden = !ui_state.overview;
+            // Negative habits are never in the overview
+            ui_state.side_ui.buttons[10].hidden =
+                ui_state.overview || user_data.activities[ui_state.current_activity].is_negative();
+            ui_state.side_ui.buttons[12].hidden = ui_state.overview;
+            ui_state.side_ui.buttons[12].highlighted =
+                matches!(ui_state.imode, InteractMode::GroupRename);
+            ui_state.side_ui.buttons[13].hidden = ui_state.overview;
+            ui_state.side_ui.buttons[15].hidden = ui_state.overview;
+            ui_state.side_ui.buttons[16].hidden = !ui_state.overview;
+            ui_state.side_ui.buttons[13].highlighted =
+                matches!(ui_state.imode, InteractMode::GoalsEdit);
             ui_state.side_ui.buttons[6].highlighted =
                 matches!(ui_state.imode, InteractMode::StartingDateSelect);
             ui_state.side_ui.buttons[7].highlighted = ui_state.edit_mode;
-            ui_state.side_ui.buttons[0].highlighted =
-                matches!(ui_state.imode, InteractMode::ActivityRename);
+            ui_state.side_ui.buttons[8].highlighted = ui_state.stats_view;
+            ui_state.side_ui.buttons[14].highlighted = ui_state.achievements_view;
+            ui_state.side_ui.buttons[0].highlighted = matches!(
+                ui_state.imode,
+                InteractMode::ActivityRename | InteractMode::ActivitySearch
+            );
         }
-        render_ctx.rw.clear(Color::WHITE);
+        if matches!(&ui_state.celebration, Some(c) if c.start.elapsed() >= CELEBRATION_DURATION) {
+            ui_state.celebration = None;
+        }
+        if instance.is_some()
+            && !autosave_interval.is_zero()
+            && last_save.elapsed() >= autosave_interval
+        {
+            // Not fatal, saving on exit will report any persisting problem
+            if let Ok(Some(_)) = save_file.save(user_data) {
+                ui_state.merged_changes(user_data, current_date);
+            }
+            last_save = Instant::now();
+        }
+        if last_sync_check.elapsed() >= SYNC_CHECK_INTERVAL {
+            last_sync_check = Instant::now();
+            // A failure might be a sync tool in the middle of writing, try again later
+            if let Ok(Some(_)) = save_file.sync(user_data) {
+                ui_state.merged_changes(user_data, current_date);
+            }
+            if matches!(instance, Some(instance) if instance.take_raise_request()) {
+                render_ctx.rw.request_focus();
+            }
+        }
+        render_ctx.rw.clear(render_ctx.palette.background);
         // Draw background
-        let mut rs = RenderStates::default();
-        let tval = (t / 64.).sin().abs();
-        bg_shader.set_uniform_float("t", tval);
-        bg_shader.set_uniform_float(
-            "cx",
-            render_ctx.rw.mouse_position().x as f32 / f32::from(RES.0),
-        );
-        bg_shader.set_uniform_float(
-            "cy",
-            1.0 - (render_ctx.rw.mouse_position().y as f32 / f32::from(RES.1)),
-        );
-        rs.set_shader(Some(&bg_shader));
-        render_ctx.rw.draw_with_renderstates(&bg_rect, &rs);
-        render::draw_calendar(&mut render_ctx, current_date, user_data, &ui_state);
-        ui_state.side_ui.draw(&mut render_ctx, user_data, &ui_state);
+        if config.theme.background_shader {
+            let mut rs = RenderStates::default();
+            let tval = (t / 64.).sin().abs();
+            bg_shader.set_uniform_float("t", tval);
+            bg_shader.set_uniform_float(
+                "cx",
+                render_ctx.rw.mouse_position().x as f32 / f32::from(RES.0),
+            );
+            bg_shader.set_uniform_float(
+                "cy",
+                1.0 - (render_ctx.rw.mouse_position().y as f32 / f32::from(RES.1)),
+            );
+            rs.set_shader(Some(&bg_shader));
+            render_ctx.rw.draw_with_renderstates(&bg_rect, &rs);
+        }
+        if ui_state.achievements_view {
+            render::draw_achievements(&mut render_ctx, user_data);
+        } else if ui_state.stats_view {
+            render::draw_stats(&mut render_ctx, user_data, &ui_state);
+        } else {
+            render::draw_calendar(&mut render_ctx, current_date, user_data, &ui_state);
+        }
+        ui_state
+            .side_ui
+            .draw(&mut render_ctx, current_date, user_data, &ui_state);
+        if let Some(celebration) = &ui_state.celebration {
+            render::draw_celebration(&mut render_ctx, celebration);
+        }
         render_ctx.rw.display();
         t += 1.0;
     }
     Ok(())
 }
 
-fn compute_n_activities_cache(cache: &mut NActivitiesCache, user_data: &UserData) {
+fn confirm_old_edit(date: NaiveDate) -> bool {
+    rfd::MessageDialog::new()
+        .set_title("Edit old day")
+        .set_description(&format!(
+            "{date} is outside of the edit window.\nDo you really want to change it?"
+        ))
+        .set_level(rfd::MessageLevel::Warning)
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+}
+
+fn show_info(title: &str, description: &str) {
+    rfd::MessageDialog::new()
+        .set_title(title)
+        .set_description(description)
+        .set_level(rfd::MessageLevel::Info)
+        .show();
+}
+
+fn show_error(title: &str, description: &str) {
+    rfd::MessageDialog::new()
+        .set_title(title)
+        .set_description(description)
+        .set_level(rfd::MessageLevel::Error)
+        .show();
+}
+
+/// Toggle `date` of the activity at `idx`, keeping the overview cache of `group` and the
+/// achievements up to date.
+///
+/// Returns a celebration if the toggle reached a goal or earned a badge.
+fn toggle_date(
+    cache: &mut NActivitiesCache,
+    user_data: &mut UserData,
+    idx: ActivityIdx,
+    group: Option<&str>,
+    date: NaiveDate,
+    current_date: NaiveDate,
+) -> Option<Celebration> {
+    let activity = &mut user_data.activities[idx];
+    let reached_before = goals::reached(activity, current_date);
+    let checked = activity.toggle_date(date);
+    activity.update_freezes(current_date);
+    if activity.counts_in_overview(group) {
+        if checked {
+            *cache.entry(date).or_insert(0) += 1;
+        } else {
+            uncount_date(cache, date);
+        }
+    }
+    let reached_goal = goals::newly_reached(activity, &reached_before, current_date);
+    let new_badges = user_data.achievements.update(&user_data.activities);
+    let message = match (new_badges.first(), reached_goal) {
+        (Some(badge), _) => format!("Badge earned: {}!", badge.name()),
+        (None, Some(goal)) => format!("Goal reached: {}!", goal.describe()),
+        (None, None) => return None,
+    };
+    Some(Celebration {
+        message,
+        start: Instant::now(),
+    })
+}
+
+/// The first activity after `current` whose name contains `query`, ignoring case,
+/// wrapping around to the start
+fn find_activity(activities: &[user_data::Activity], query: &str, current: usize) -> Option<usize> {
+    let query = query.to_lowercase();
+    (current + 1..activities.len())
+        .chain(0..=current)
+        .find(|&i| activities[i].name.to_lowercase().contains(&query))
+}
+
+struct Celebration {
+    message: String,
+    start: Instant,
+}
+
+fn uncount_date(cache: &mut NActivitiesCache, date: NaiveDate) {
+    if let Some(count) = cache.get_mut(&date) {
+        *count -= 1;
+        if *count == 0 {
+            cache.remove(&date);
+        }
+    }
+}
+
+fn compute_n_activities_cache(
+    cache: &mut NActivitiesCache,
+    user_data: &UserData,
+    group: Option<&str>,
+) {
     cache.clear();
-    for ac in &user_data.activities {
+    for ac in user_data.overview_activities(group) {
         for date in &ac.dates {
             *cache.entry(*date).or_insert(0) += 1;
         }
@@ -298,7 +820,7 @@ struct DayBox {
     date: NaiveDate,
 }
 
-fn gen_day_boxes(date: NaiveDate) -> Vec<DayBox> {
+fn gen_day_boxes(date: NaiveDate, week_start: WeekStart) -> Vec<DayBox> {
     let mut boxes = Vec::new();
     let curr_month = date.month();
     for m in 0..12 {
@@ -307,10 +829,11 @@ fn gen_day_boxes(date: NaiveDate) -> Vec<DayBox> {
             date_util::month_year_offset(curr_month as i32, date.year(), month_offset);
         let (x, y) = month_box_pixel_position(m);
         let n_days = date_util::days_in_month(actual_year, actual_month as u8);
-        let weekday_offset = NaiveDate::from_ymd_opt(actual_year, actual_month as u32, 1)
-            .unwrap()
-            .weekday()
-            .num_days_from_monday() as u8;
+        let weekday_offset = week_start.column(
+            NaiveDate::from_ymd_opt(actual_year, actual_month as u32, 1)
+                .unwrap()
+                .weekday(),
+        );
         for index in weekday_offset..n_days + weekday_offset {
             let dx = (index % DAYS_PER_WEEK) * (DAYBOX_SIZE + DAYBOX_PADDING);
             let dy = (index / DAYS_PER_WEEK) * (DAYBOX_SIZE + DAYBOX_PADDING);
@@ -330,6 +853,124 @@ fn gen_day_boxes(date: NaiveDate) -> Vec<DayBox> {
     boxes
 }
 
+#[test]
+fn test_gen_day_boxes_week_start() {
+    // The 1st of January 2023 is a Sunday
+    let date = NaiveDate::from_ymd_opt(2023, 1, 15).unwrap();
+    let first_of_month = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let (month_x, month_y) = month_box_pixel_position(CURRENT_MONTH_OFFSET);
+    let step = u16::from(DAYBOX_SIZE + DAYBOX_PADDING);
+    for (week_start, column) in [
+        (WeekStart::Monday, 6),
+        (WeekStart::Sunday, 0),
+        (WeekStart::Saturday, 1),
+    ] {
+        let boxes = gen_day_boxes(date, week_start);
+        let first = boxes.iter().find(|b| b.date == first_of_month).unwrap();
+        let left = month_x as u16 + u16::from(MONTH_BOX_PADDING);
+        assert_eq!(first.x, left + column * step);
+        // The 8th is always on the row below, in the same column
+        let eighth = boxes
+            .iter()
+            .find(|b| b.date == NaiveDate::from_ymd_opt(2023, 1, 8).unwrap())
+            .unwrap();
+        assert_eq!((eighth.x, eighth.y), (first.x, first.y + step));
+        // The 31st is a Tuesday
+        let last = boxes
+            .iter()
+            .find(|b| b.date == NaiveDate::from_ymd_opt(2023, 1, 31).unwrap())
+            .unwrap();
+        assert_eq!(
+            last.x,
+            left + u16::from(week_start.column(Weekday::Tue)) * step
+        );
+        assert!(first.y >= month_y as u16);
+    }
+}
+
+#[test]
+fn test_change_date() {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let next_date = date.succ_opt().unwrap();
+    let mut user_data = UserData::new_default(date);
+    user_data.activities[0].dates.insert(date);
+    let mut ui_state = UiState::new(date, WeekStart::Monday, Language::English);
+    ui_state.update_streaks(&mut user_data, date);
+    assert_eq!(ui_state.current_streak, 1);
+    // The month after the current one is displayed as well
+    let end_of_march = NaiveDate::from_ymd_opt(2023, 3, 31).unwrap();
+    assert!(!ui_state.day_boxes.iter().any(|b| b.date == end_of_march));
+    ui_state.change_date(&mut user_data, next_date);
+    assert!(ui_state.day_boxes.iter().any(|b| b.date == end_of_march));
+    assert_eq!(ui_state.current_streak, 0);
+    assert_eq!(ui_state.longest_streak, 1);
+    assert_eq!(ui_state.n_activities_cache.get(&date), Some(&1));
+}
+
+#[test]
+fn test_group_navigation() {
+    use button::Id::*;
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut user_data = UserData::new_default(date);
+    for _ in 0..3 {
+        user_data.insert_default_activity(0, date);
+    }
+    user_data.activities[1].group = "Health".to_owned();
+    user_data.activities[3].group = "Health".to_owned();
+    user_data.activities[3].dates.insert(date);
+    user_data.activities[2].group = "Work".to_owned();
+    user_data.activities[2].dates.insert(date);
+    let mut ui_state = UiState::new(date, WeekStart::Monday, Language::English);
+    ui_state.press_button(GroupFilter, &mut user_data, date);
+    assert_eq!(ui_state.group.as_deref(), Some("Health"));
+    assert_eq!(ui_state.current_activity, 1);
+    assert_eq!(ui_state.n_activities_cache.get(&date), Some(&1));
+    ui_state.press_button(NextActivity, &mut user_data, date);
+    assert_eq!(ui_state.current_activity, 3);
+    ui_state.press_button(NextActivity, &mut user_data, date);
+    assert_eq!(ui_state.current_activity, 3);
+    ui_state.press_button(PrevActivity, &mut user_data, date);
+    assert_eq!(ui_state.current_activity, 1);
+    ui_state.press_button(AddActivity, &mut user_data, date);
+    assert_eq!(user_data.activities[2].group, "Health");
+    ui_state.press_button(GroupFilter, &mut user_data, date);
+    assert_eq!(ui_state.group.as_deref(), Some("Work"));
+    assert_eq!(ui_state.current_activity, 3);
+    // Removing the last activity of a group shows all activities again
+    ui_state.press_button(RemActivity, &mut user_data, date);
+    assert_eq!(ui_state.group, None);
+    assert_eq!(ui_state.n_activities_cache.get(&date), Some(&1));
+    ui_state.press_button(GroupFilter, &mut user_data, date);
+    ui_state.press_button(GroupFilter, &mut user_data, date);
+    assert_eq!(ui_state.group, None);
+}
+
+#[test]
+fn test_find_activity() {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut user_data = UserData::new_default(date);
+    for i in 1..=300 {
+        user_data.insert_default_activity(i, date);
+        user_data.activities[i].name = format!("Activity {i}");
+    }
+    let activities = &user_data.activities;
+    assert_eq!(find_activity(activities, "activity 42", 0), Some(42));
+    assert_eq!(find_activity(activities, "UNNAMED", 0), Some(0));
+    // Searching again finds the next match, wrapping around
+    assert_eq!(find_activity(activities, "activity 29", 29), Some(290));
+    assert_eq!(find_activity(activities, "activity 29", 299), Some(29));
+    assert_eq!(find_activity(activities, "nothing", 0), None);
+    // Adding past 256 activities doesn't overflow
+    let mut ui_state = UiState::new(date, WeekStart::Monday, Language::English);
+    ui_state.current_activity = 300;
+    ui_state.press_button(button::Id::AddActivity, &mut user_data, date);
+    assert_eq!(ui_state.current_activity, 301);
+    ui_state.select_end(&user_data, false);
+    assert_eq!(ui_state.current_activity, 0);
+    ui_state.select_end(&user_data, true);
+    assert_eq!(ui_state.current_activity, 301);
+}
+
 struct SideUi {
     buttons: Vec<Button>,
 }
@@ -351,4 +992,7 @@ enum InteractMode {
     Default,
     StartingDateSelect,
     ActivityRename,
+    GroupRename,
+    GoalsEdit,
+    ActivitySearch,
 }
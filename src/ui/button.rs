@@ -1,5 +1,6 @@
 use sfml::graphics::Rect;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Id {
     CurrentActivity,
     PrevActivity,
@@ -9,6 +10,31 @@ pub enum Id {
     Overview,
     SetStartingDate,
     EditMode,
+    Statistics,
+    StreakMode,
+    InOverview,
+    GroupFilter,
+    ActivityGroup,
+    Goals,
+    Achievements,
+    Polarity,
+    MergeSaveFile,
+}
+
+impl Id {
+    /// Whether pressing the button changes the data, which the read-only window can't do
+    pub fn edits_data(self) -> bool {
+        !matches!(
+            self,
+            Id::PrevActivity
+                | Id::NextActivity
+                | Id::Overview
+                | Id::Statistics
+                | Id::StreakMode
+                | Id::GroupFilter
+                | Id::Achievements
+        )
+    }
 }
 
 pub struct Button {
@@ -48,6 +74,15 @@ pub fn buttons() -> Vec<Button> {
        1024, 52,             24, 24, NextActivity,    Sprite,
         904, 82,            178, 32, Overview,        RectWithText,
         904, 82 + 42,       178, 32, SetStartingDate, RectWithText,
-        904, 82 + (2 * 42), 178, 32, EditMode,        RectWithText
+        904, 82 + (2 * 42), 178, 32, EditMode,        RectWithText,
+        904, 82 + (3 * 42), 178, 32, Statistics,      RectWithText,
+        904, 82 + (4 * 42), 178, 32, StreakMode,      RectWithText,
+        904, 82 + (4 * 42), 178, 32, InOverview,      RectWithText,
+        904, 444,           178, 32, GroupFilter,     RectWithText,
+        904, 444 + 42,      178, 32, ActivityGroup,   RectWithText,
+        904, 444 + (2 * 42), 178, 32, Goals,          RectWithText,
+        904, 82 + (5 * 42), 178, 32, Achievements,    RectWithText,
+        904, 444 + (3 * 42), 178, 32, Polarity,       RectWithText,
+        904, 444 + (3 * 42), 178, 32, MergeSaveFile,  RectWithText
     }
 }
@@ -1,4 +1,25 @@
+use crate::config::{Rgb, Theme};
 use sfml::graphics::Color;
 
-pub const GOLD: Color = Color::rgb(231, 183, 13);
-pub const GOLD_BRIGHTER: Color = Color::rgb(255, 222, 92);
+/// The colors of the configured theme, converted for rendering
+pub struct Palette {
+    pub accent: Color,
+    pub accent_bright: Color,
+    pub background: Color,
+}
+
+impl From<Rgb> for Color {
+    fn from(Rgb(r, g, b): Rgb) -> Self {
+        Color::rgb(r, g, b)
+    }
+}
+
+impl From<&Theme> for Palette {
+    fn from(theme: &Theme) -> Self {
+        Self {
+            accent: theme.accent.into(),
+            accent_bright: theme.accent_bright.into(),
+            background: theme.background.into(),
+        }
+    }
+}
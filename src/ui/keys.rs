@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use sfml::window::{Event, Key};
+use std::fmt;
+
+/// A key with modifiers, written like "Ctrl+Shift+O" in the config file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyCombo {
+    key: Key,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl KeyCombo {
+    pub const fn plain(key: Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+    pub const fn ctrl(key: Key) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::plain(key)
+        }
+    }
+    pub fn matches(&self, event: &Event) -> bool {
+        match *event {
+            Event::KeyPressed {
+                code,
+                ctrl,
+                shift,
+                alt,
+                ..
+            } => (code, ctrl, shift, alt) == (self.key, self.ctrl, self.shift, self.alt),
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = String;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        let mut parts: Vec<&str> = string.split('+').map(str::trim).collect();
+        let key_name = parts.pop().unwrap_or_default();
+        let key = key_from_name(key_name)
+            .ok_or_else(|| format!("Unknown key \"{key_name}\" in key binding \"{string}\""))?;
+        let mut combo = Self::plain(key);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => combo.ctrl = true,
+                "shift" => combo.shift = true,
+                "alt" => combo.alt = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown modifier \"{modifier}\" in key binding \"{string}\""
+                    ))
+                }
+            }
+        }
+        Ok(combo)
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|(_, key)| *key == self.key)
+            .map_or("?", |(name, _)| name);
+        write!(f, "{name}")
+    }
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|(_, key)| *key)
+}
+
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("A", Key::A),
+    ("B", Key::B),
+    ("C", Key::C),
+    ("D", Key::D),
+    ("E", Key::E),
+    ("F", Key::F),
+    ("G", Key::G),
+    ("H", Key::H),
+    ("I", Key::I),
+    ("J", Key::J),
+    ("K", Key::K),
+    ("L", Key::L),
+    ("M", Key::M),
+    ("N", Key::N),
+    ("O", Key::O),
+    ("P", Key::P),
+    ("Q", Key::Q),
+    ("R", Key::R),
+    ("S", Key::S),
+    ("T", Key::T),
+    ("U", Key::U),
+    ("V", Key::V),
+    ("W", Key::W),
+    ("X", Key::X),
+    ("Y", Key::Y),
+    ("Z", Key::Z),
+    ("0", Key::Num0),
+    ("1", Key::Num1),
+    ("2", Key::Num2),
+    ("3", Key::Num3),
+    ("4", Key::Num4),
+    ("5", Key::Num5),
+    ("6", Key::Num6),
+    ("7", Key::Num7),
+    ("8", Key::Num8),
+    ("9", Key::Num9),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Insert", Key::Insert),
+    ("Delete", Key::Delete),
+    ("Space", Key::Space),
+    ("Tab", Key::Tab),
+    ("Escape", Key::Escape),
+];
+
+#[test]
+fn test_parse_key_combo() {
+    let parse = |s: &str| KeyCombo::try_from(s.to_owned());
+    assert_eq!(parse("o"), Ok(KeyCombo::plain(Key::O)));
+    assert_eq!(parse("Ctrl+Q"), Ok(KeyCombo::ctrl(Key::Q)));
+    assert_eq!(
+        parse("ctrl + shift + PageUp").map(|c| c.to_string()),
+        Ok("Ctrl+Shift+PageUp".to_owned())
+    );
+    assert!(parse("Hyper+A").is_err());
+    assert!(parse("Ctrl+").is_err());
+    assert!(parse("Foo").is_err());
+}
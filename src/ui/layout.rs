@@ -1,4 +1,4 @@
-use crate::date_util::{DAYS_PER_WEEK, MONTHS_PER_YEAR};
+use calen_do::date_util::{DAYS_PER_WEEK, MONTHS_PER_YEAR};
 
 pub const DAYBOX_SIZE: u8 = 24;
 pub const DAYBOX_PADDING: u8 = 6;
@@ -1,19 +1,147 @@
-use crate::date_util::{DAYS_PER_WEEK, MONTHS_PER_YEAR};
-
-pub const WEEKDAY_NAMES_2: [&str; DAYS_PER_WEEK as usize] =
-    ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
-
-pub const MONTH_NAMES: [&str; MONTHS_PER_YEAR as usize] = [
-    "January",
-    "Febuary",
-    "March",
-    "April",
-    "May",
-    "June",
-    "July",
-    "August",
-    "September",
-    "October",
-    "November",
-    "December",
-];
+use calen_do::date_util::{DAYS_PER_WEEK, MONTHS_PER_YEAR};
+use chrono::Weekday;
+use serde::Deserialize;
+
+/// Names of calendar related things in a specific language
+pub struct Names {
+    /// Two letter weekday abbreviations, starting with Monday
+    weekdays_2: [&'static str; DAYS_PER_WEEK as usize],
+    months: [&'static str; MONTHS_PER_YEAR as usize],
+}
+
+impl Names {
+    pub fn weekday_2(&self, weekday: Weekday) -> &'static str {
+        self.weekdays_2[weekday.num_days_from_monday() as usize]
+    }
+    /// Name of a month, 1 being January
+    pub fn month(&self, month: u32) -> &'static str {
+        self.months[(month - 1) as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Language {
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "de")]
+    German,
+    #[serde(rename = "fr")]
+    French,
+    #[serde(rename = "es")]
+    Spanish,
+    #[serde(rename = "hu")]
+    Hungarian,
+}
+
+impl Language {
+    /// Look up a language by its ISO 639-1 code, like "en" or "hu"
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "en" => Some(Self::English),
+            "de" => Some(Self::German),
+            "fr" => Some(Self::French),
+            "es" => Some(Self::Spanish),
+            "hu" => Some(Self::Hungarian),
+            _ => None,
+        }
+    }
+    pub fn names(self) -> &'static Names {
+        match self {
+            Self::English => &ENGLISH,
+            Self::German => &GERMAN,
+            Self::French => &FRENCH,
+            Self::Spanish => &SPANISH,
+            Self::Hungarian => &HUNGARIAN,
+        }
+    }
+}
+
+const ENGLISH: Names = Names {
+    weekdays_2: ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"],
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+};
+
+const GERMAN: Names = Names {
+    weekdays_2: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+};
+
+const FRENCH: Names = Names {
+    weekdays_2: ["Lu", "Ma", "Me", "Je", "Ve", "Sa", "Di"],
+    months: [
+        "Janvier",
+        "Février",
+        "Mars",
+        "Avril",
+        "Mai",
+        "Juin",
+        "Juillet",
+        "Août",
+        "Septembre",
+        "Octobre",
+        "Novembre",
+        "Décembre",
+    ],
+};
+
+const SPANISH: Names = Names {
+    weekdays_2: ["Lu", "Ma", "Mi", "Ju", "Vi", "Sá", "Do"],
+    months: [
+        "Enero",
+        "Febrero",
+        "Marzo",
+        "Abril",
+        "Mayo",
+        "Junio",
+        "Julio",
+        "Agosto",
+        "Septiembre",
+        "Octubre",
+        "Noviembre",
+        "Diciembre",
+    ],
+};
+
+const HUNGARIAN: Names = Names {
+    weekdays_2: ["Hé", "Ke", "Sze", "Cs", "Pé", "Szo", "Va"],
+    months: [
+        "Január",
+        "Február",
+        "Március",
+        "Április",
+        "Május",
+        "Június",
+        "Július",
+        "Augusztus",
+        "Szeptember",
+        "Október",
+        "November",
+        "December",
+    ],
+};
@@ -1,9 +1,23 @@
-use super::{button, color, layout::*, names::*, InteractMode, SideUi, UiState};
-use crate::{date_util, UserData};
+use super::{
+    button, color::Palette, layout::*, text_field::TextField, Celebration, InteractMode, SideUi,
+    UiState, CELEBRATION_DURATION,
+};
+use crate::config::Theme;
+use calen_do::{
+    achievements::Badge,
+    date_util::{self, DAYS_PER_WEEK},
+    stats::{self, Rate, RECENT_WINDOWS, ROLLING_WINDOW},
+    user_data::{Activity, UserData},
+};
 use chrono::prelude::*;
 use sfml::{graphics::*, window::*, SfBox};
 use std::error::Error;
 
+/// Tint of the relapse days of negative habits
+const RELAPSE_COLOR: Color = Color::rgb(255, 110, 110);
+/// Outline of missed days covered by a streak freeze
+const FROZEN_COLOR: Color = Color::rgb(90, 170, 255);
+
 pub struct Resources {
     font: SfBox<Font>,
     sprite_sheet: SfBox<Texture>,
@@ -25,10 +39,11 @@ pub struct RenderContext<'res> {
     text: Text<'res>,
     sprite: Sprite<'res>,
     pub rw: RenderWindow,
+    pub palette: Palette,
 }
 
 impl<'res> RenderContext<'res> {
-    pub fn with_resources(res: &'res Resources) -> Self {
+    pub fn new(res: &'res Resources, theme: &Theme) -> Self {
         let mut rw = RenderWindow::new(
             (RES.0.into(), RES.1.into()),
             "Calen-Do!",
@@ -40,6 +55,7 @@ impl<'res> RenderContext<'res> {
             text: Text::new("", &res.font, 16),
             sprite: Sprite::with_texture(&res.sprite_sheet),
             rw,
+            palette: theme.into(),
         }
     }
 }
@@ -68,7 +84,7 @@ pub(super) fn draw_calendar(
         let (x, y) = month_box_pixel_position(m);
         if m == CURRENT_MONTH_OFFSET {
             rect.set_position((x, y));
-            rect.set_outline_color(color::GOLD);
+            rect.set_outline_color(render_ctx.palette.accent);
             rect.set_outline_thickness(2.0);
             render_ctx.rw.draw(&rect);
         }
@@ -78,7 +94,7 @@ pub(super) fn draw_calendar(
             y as i16 + i16::from(MONTH_BOX_PADDING),
             &format!(
                 "{} {}",
-                MONTH_NAMES[(actual_month - 1) as usize],
+                ui_state.names.month(actual_month as u32),
                 actual_year,
             ),
         );
@@ -88,43 +104,74 @@ pub(super) fn draw_calendar(
                 (x as i16 + wd * i16::from(DAYBOX_SIZE + DAYBOX_PADDING))
                     + i16::from(MONTH_BOX_PADDING),
                 y as i16 + i16::from(MONTH_BOX_PADDING) + 16 + i16::from(MONTH_BOX_PADDING),
-                WEEKDAY_NAMES_2[wd as usize],
+                ui_state
+                    .names
+                    .weekday_2(ui_state.week_start.weekday_at(wd as u8)),
             );
         }
     }
+    let group = ui_state.group.as_deref();
+    let overview_activities: Vec<_> = user_data.overview_activities(group).collect();
+    let current = &user_data.activities[ui_state.current_activity];
     for day_box in &ui_state.day_boxes {
         let starting_date = if ui_state.overview {
-            user_data
-                .activities
-                .iter()
-                .min_by_key(|act| act.starting_date)
-                .unwrap()
-                .starting_date
+            // Nothing to show if no activity is in the overview
+            user_data.overview_start(group).unwrap_or(NaiveDate::MAX)
         } else {
-            user_data.activities[ui_state.current_activity as usize].starting_date
+            user_data.activities[ui_state.current_activity].starting_date
         };
-        if day_box.date >= starting_date && day_box.date <= date {
+        // Future days are only shown if they were checked in edit mode
+        let checked = !ui_state.overview
+            && user_data.activities[ui_state.current_activity]
+                .dates
+                .contains(&day_box.date);
+        if day_box.date >= starting_date && (day_box.date <= date || checked) {
             render_ctx
                 .sprite
                 .set_position((f32::from(day_box.x), f32::from(day_box.y)));
+            let mut perfect = false;
+            let mut frozen = false;
             if ui_state.overview {
-                let n_activities = *ui_state.n_activities_cache.get(&day_box.date).unwrap_or(&0);
-                let (sprite_idx, text_color) = match n_activities {
-                    0 => (1, Color::WHITE),
-                    1 => (0, color::GOLD_BRIGHTER),
-                    2 => (6, color::GOLD_BRIGHTER),
-                    _ => (7, color::GOLD_BRIGHTER),
-                };
-                render_ctx.text.set_fill_color(text_color);
+                let completion = stats::day_completion(&overview_activities, day_box.date);
+                let fraction = completion.fraction().unwrap_or(0.0);
+                perfect = completion.total != 0 && completion.done == completion.total;
+                // The most intense box is blended over an empty one by the fraction done
                 render_ctx
                     .sprite
-                    .set_texture_rect(IntRect::new(sprite_idx * 24, 0, 24, 24));
-            } else if user_data.activities[ui_state.current_activity as usize]
-                .dates
+                    .set_texture_rect(IntRect::new(24, 0, 24, 24));
+                render_ctx.rw.draw(&render_ctx.sprite);
+                render_ctx
+                    .sprite
+                    .set_color(Color::rgba(255, 255, 255, (fraction * 255.0) as u8));
+                render_ctx
+                    .sprite
+                    .set_texture_rect(IntRect::new(7 * 24, 0, 24, 24));
+                render_ctx.text.set_fill_color(if completion.done == 0 {
+                    Color::WHITE
+                } else {
+                    render_ctx.palette.accent_bright
+                });
+            } else if user_data.activities[ui_state.current_activity]
+                .freezes
+                .covered
                 .contains(&day_box.date)
             {
+                // Missed, but a freeze saved the streak
+                render_ctx.text.set_fill_color(Color::WHITE);
+                render_ctx.sprite.set_color(Color::rgb(160, 210, 255));
+                render_ctx.sprite.set_texture_rect(IntRect::new(
+                    i32::from(DAYBOX_SIZE),
+                    0,
+                    i32::from(DAYBOX_SIZE),
+                    i32::from(DAYBOX_SIZE),
+                ));
+                frozen = true;
+            } else if current.dates.contains(&day_box.date) != current.is_negative() {
+                // Done, or a clean day of a negative habit
                 if day_box.date == date {
-                    render_ctx.text.set_fill_color(color::GOLD_BRIGHTER);
+                    render_ctx
+                        .text
+                        .set_fill_color(render_ctx.palette.accent_bright);
                 } else {
                     render_ctx.text.set_fill_color(Color::BLACK);
                 }
@@ -136,10 +183,15 @@ pub(super) fn draw_calendar(
                 ));
             } else {
                 if day_box.date == date {
-                    render_ctx.text.set_fill_color(color::GOLD_BRIGHTER);
+                    render_ctx
+                        .text
+                        .set_fill_color(render_ctx.palette.accent_bright);
                 } else {
                     render_ctx.text.set_fill_color(Color::WHITE);
                 }
+                if current.is_negative() {
+                    render_ctx.sprite.set_color(RELAPSE_COLOR);
+                }
                 render_ctx.sprite.set_texture_rect(IntRect::new(
                     i32::from(DAYBOX_SIZE),
                     0,
@@ -148,11 +200,23 @@ pub(super) fn draw_calendar(
                 ));
             }
             render_ctx.rw.draw(&render_ctx.sprite);
+            render_ctx.sprite.set_color(Color::WHITE);
+            if perfect || frozen {
+                rect.set_outline_color(if frozen {
+                    FROZEN_COLOR
+                } else {
+                    render_ctx.palette.accent_bright
+                });
+                rect.set_outline_thickness(2.0);
+                rect.set_size((f32::from(DAYBOX_SIZE) - 4.0, f32::from(DAYBOX_SIZE) - 4.0));
+                rect.set_position((f32::from(day_box.x) + 2.0, f32::from(day_box.y) + 2.0));
+                render_ctx.rw.draw(&rect);
+            }
         } else {
             render_ctx.text.set_fill_color(Color::BLACK);
         }
         if day_box.date == date {
-            rect.set_outline_color(color::GOLD);
+            rect.set_outline_color(render_ctx.palette.accent);
             rect.set_outline_thickness(2.0);
             rect.set_size((f32::from(DAYBOX_SIZE), f32::from(DAYBOX_SIZE)));
             rect.set_position((f32::from(day_box.x), f32::from(day_box.y)));
@@ -167,6 +231,241 @@ pub(super) fn draw_calendar(
     }
 }
 
+fn percent(rate: Rate) -> String {
+    match rate.fraction() {
+        Some(fraction) => format!(
+            "{:>3.0}% ({} of {} days)",
+            fraction * 100.0,
+            rate.done,
+            rate.total
+        ),
+        None => "-".to_owned(),
+    }
+}
+
+/// Darkens the calendar area, and returns its top left corner
+fn draw_backdrop(render_ctx: &mut RenderContext) -> (f32, f32) {
+    let (x, y) = month_box_pixel_position(0);
+    let mut rs = RectangleShape::new();
+    rs.set_fill_color(Color::rgba(0, 0, 0, 180));
+    rs.set_position((x, y));
+    rs.set_size((
+        f32::from(MONTH_BOX_SIZE.0 + u16::from(MONTH_BOX_PADDING) + u16::from(MONTH_BOX_MARGIN))
+            * f32::from(MONTHS_PER_ROW)
+            - f32::from(MONTH_BOX_MARGIN),
+        f32::from(CALENDAR_SIZE.1) - f32::from(MONTH_BOX_MARGIN),
+    ));
+    render_ctx.rw.draw(&rs);
+    (x, y)
+}
+
+pub(super) fn draw_achievements(render_ctx: &mut RenderContext, user_data: &UserData) {
+    let (x, y) = draw_backdrop(render_ctx);
+    render_ctx.text.set_fill_color(Color::WHITE);
+    draw_text(render_ctx, x as i16 + 16, y as i16 + 16, "Achievements");
+    const ROW_HEIGHT: f32 = 64.0;
+    let mut rs = RectangleShape::new();
+    for (i, badge) in Badge::ALL.into_iter().enumerate() {
+        let row_y = y + 64.0 + i as f32 * ROW_HEIGHT;
+        let earned = user_data.achievements.earned.get(&badge);
+        // Badge icon, filled in once earned
+        rs.set_position((x + 16.0, row_y));
+        rs.set_size((40.0, 40.0));
+        rs.set_outline_thickness(2.0);
+        if earned.is_some() {
+            rs.set_fill_color(render_ctx.palette.accent);
+            rs.set_outline_color(render_ctx.palette.accent_bright);
+        } else {
+            rs.set_fill_color(Color::TRANSPARENT);
+            rs.set_outline_color(Color::rgba(255, 255, 255, 96));
+        }
+        render_ctx.rw.draw(&rs);
+        render_ctx.text.set_fill_color(if earned.is_some() {
+            render_ctx.palette.accent_bright
+        } else {
+            Color::rgba(255, 255, 255, 128)
+        });
+        draw_text(render_ctx, x as i16 + 72, row_y as i16, badge.name());
+        render_ctx
+            .text
+            .set_fill_color(Color::rgba(255, 255, 255, 160));
+        let detail = match earned {
+            Some(date) => format!("{} - earned on {date}", badge.description()),
+            None => badge.description().to_owned(),
+        };
+        draw_text(render_ctx, x as i16 + 72, row_y as i16 + 20, &detail);
+    }
+}
+
+pub(super) fn draw_stats(render_ctx: &mut RenderContext, user_data: &UserData, ui_state: &UiState) {
+    let (x, y) = draw_backdrop(render_ctx);
+    let stats = &ui_state.stats;
+    let title = if ui_state.overview {
+        match &ui_state.group {
+            Some(group) => format!("Overview of {group}"),
+            None => "Overview".to_owned(),
+        }
+    } else {
+        user_data.activities[ui_state.current_activity].name.clone()
+    };
+    let mut lines = vec![
+        title,
+        String::new(),
+        format!("Completion:     {}", percent(stats.overall)),
+    ];
+    for (window, rate) in RECENT_WINDOWS.iter().zip(stats.recent) {
+        lines.push(format!("Last {window:>3} days: {}", percent(rate)));
+    }
+    lines.push(String::new());
+    lines.push(format!("Total days done: {}", stats.overall.done));
+    lines.push(format!("Streaks: {}", stats.streak_count));
+    lines.push(format!("Average streak: {:.1} days", stats.average_streak));
+    lines.push(format!("Median streak:  {:.1} days", stats.median_streak));
+    lines.push(match stats.best_month {
+        Some(best) => format!(
+            "Best month: {} {} ({} days)",
+            ui_state.names.month(best.month),
+            best.year,
+            best.done
+        ),
+        None => "Best month: -".to_owned(),
+    });
+    lines.push(String::new());
+    lines.push("Weekdays:".to_owned());
+    for column in 0..DAYS_PER_WEEK {
+        let weekday = ui_state.week_start.weekday_at(column);
+        lines.push(format!(
+            "  {:<3} {}",
+            ui_state.names.weekday_2(weekday),
+            percent(stats.weekdays[weekday.num_days_from_monday() as usize])
+        ));
+    }
+    render_ctx.text.set_fill_color(Color::WHITE);
+    const LINE_HEIGHT: i16 = 22;
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            render_ctx,
+            x as i16 + 16,
+            y as i16 + 16 + i as i16 * LINE_HEIGHT,
+            line,
+        );
+    }
+    let chart_x = x + 480.0;
+    draw_text(
+        render_ctx,
+        chart_x as i16,
+        y as i16 + 16,
+        &format!("{ROLLING_WINDOW} day completion rate"),
+    );
+    draw_line_chart(
+        render_ctx,
+        FloatRect::new(chart_x, y + 48.0, 380.0, 260.0),
+        &stats.rolling_rate,
+    );
+    draw_text(
+        render_ctx,
+        chart_x as i16,
+        y as i16 + 360,
+        "Completion by weekday",
+    );
+    let bars: Vec<_> = (0..DAYS_PER_WEEK)
+        .map(|column| {
+            let weekday = ui_state.week_start.weekday_at(column);
+            (
+                ui_state.names.weekday_2(weekday),
+                stats.weekdays[weekday.num_days_from_monday() as usize].fraction(),
+            )
+        })
+        .collect();
+    draw_bar_chart(
+        render_ctx,
+        FloatRect::new(chart_x, y + 392.0, 380.0, 260.0),
+        &bars,
+    );
+}
+
+/// Background, 0%/50%/100% grid lines and their labels
+fn draw_chart_frame(render_ctx: &mut RenderContext, area: FloatRect) {
+    let mut rs = RectangleShape::new();
+    rs.set_fill_color(Color::rgba(255, 255, 255, 24));
+    rs.set_position((area.left, area.top));
+    rs.set_size((area.width, area.height));
+    render_ctx.rw.draw(&rs);
+    rs.set_fill_color(Color::rgba(255, 255, 255, 64));
+    render_ctx
+        .text
+        .set_fill_color(Color::rgba(255, 255, 255, 160));
+    for (fraction, label) in [(0.0, "0%"), (0.5, "50%"), (1.0, "100%")] {
+        let line_y = area.top + area.height * (1.0 - fraction);
+        rs.set_position((area.left, line_y));
+        rs.set_size((area.width, 1.0));
+        render_ctx.rw.draw(&rs);
+        draw_text(
+            render_ctx,
+            area.left as i16 - 44,
+            line_y as i16 - 10,
+            &format!("{label:>4}"),
+        );
+    }
+    render_ctx.text.set_fill_color(Color::WHITE);
+}
+
+/// Line chart of values between 0 and 1, spread evenly over the width of `area`
+fn draw_line_chart(render_ctx: &mut RenderContext, area: FloatRect, values: &[f32]) {
+    draw_chart_frame(render_ctx, area);
+    if values.len() < 2 {
+        return;
+    }
+    // No point in having more vertices than pixels
+    let n_points = values.len().min(area.width as usize);
+    let color = render_ctx.palette.accent_bright;
+    let vertices: Vec<Vertex> = (0..n_points)
+        .map(|i| {
+            let progress = i as f32 / (n_points - 1) as f32;
+            let value = values[(progress * (values.len() - 1) as f32).round() as usize];
+            Vertex::with_pos_color(
+                (
+                    area.left + progress * area.width,
+                    area.top + area.height * (1.0 - value),
+                )
+                    .into(),
+                color,
+            )
+        })
+        .collect();
+    render_ctx.rw.draw_primitives(
+        &vertices,
+        PrimitiveType::LINE_STRIP,
+        &RenderStates::default(),
+    );
+}
+
+/// Bar chart of labeled values between 0 and 1. Bars without a value are left empty.
+fn draw_bar_chart(render_ctx: &mut RenderContext, area: FloatRect, bars: &[(&str, Option<f32>)]) {
+    draw_chart_frame(render_ctx, area);
+    let slot_width = area.width / bars.len() as f32;
+    let mut rs = RectangleShape::new();
+    rs.set_fill_color(render_ctx.palette.accent);
+    for (i, &(label, value)) in bars.iter().enumerate() {
+        let bar_x = area.left + i as f32 * slot_width;
+        if let Some(value) = value {
+            let bar_height = area.height * value;
+            rs.set_position((
+                bar_x + slot_width / 4.0,
+                area.top + area.height - bar_height,
+            ));
+            rs.set_size((slot_width / 2.0, bar_height));
+            render_ctx.rw.draw(&rs);
+        }
+        draw_text(
+            render_ctx,
+            (bar_x + slot_width / 2.0) as i16 - 10,
+            (area.top + area.height) as i16 + 4,
+            label,
+        );
+    }
+}
+
 fn draw_rect_with_text(
     render_ctx: &mut RenderContext,
     x: f32,
@@ -233,6 +532,60 @@ fn draw_text_wrapped(render_ctx: &mut RenderContext, string: &str, x: f32, y: f3
     render_ctx.rw.draw(&render_ctx.text);
 }
 
+fn draw_text_field(
+    render_ctx: &mut RenderContext,
+    rect: FloatRect,
+    field: &TextField,
+    highlighted: bool,
+) {
+    // Draw the box only, the text is drawn left aligned on a single line while editing
+    draw_rect_with_text(
+        render_ctx,
+        rect.left,
+        rect.top,
+        rect.width,
+        rect.height,
+        "",
+        highlighted,
+    );
+    const PADDING: f32 = 4.0;
+    let text_y = rect.top + (rect.height - render_ctx.text.character_size() as f32) / 3.0;
+    render_ctx.text.set_string(field.text());
+    render_ctx.text.set_position((rect.left + PADDING, text_y));
+    // Scroll the text to the left if the cursor would be outside the box
+    let cursor_x = render_ctx
+        .text
+        .find_character_pos(field.cursor_char_idx())
+        .x;
+    let overflow = cursor_x - (rect.left + rect.width - PADDING);
+    if overflow > 0.0 {
+        render_ctx
+            .text
+            .set_position((rect.left + PADDING - overflow, text_y));
+    }
+    let line_height = render_ctx.text.character_size() as f32 + 4.0;
+    if let Some(range) = field.selection_char_range() {
+        let start_x = render_ctx.text.find_character_pos(range.start).x;
+        let end_x = render_ctx.text.find_character_pos(range.end).x;
+        let mut sel_rect = RectangleShape::new();
+        sel_rect.set_fill_color(Color::rgba(0, 0, 255, 160));
+        sel_rect.set_position((start_x, text_y));
+        sel_rect.set_size((end_x - start_x, line_height));
+        render_ctx.rw.draw(&sel_rect);
+    }
+    render_ctx.text.set_fill_color(Color::WHITE);
+    render_ctx.rw.draw(&render_ctx.text);
+    let cursor_x = render_ctx
+        .text
+        .find_character_pos(field.cursor_char_idx())
+        .x;
+    let mut cursor_rect = RectangleShape::new();
+    cursor_rect.set_fill_color(render_ctx.palette.accent_bright);
+    cursor_rect.set_position((cursor_x, text_y));
+    cursor_rect.set_size((2.0, line_height));
+    render_ctx.rw.draw(&cursor_rect);
+}
+
 impl button::Button {
     fn draw(&self, render_ctx: &mut RenderContext, user_data: &UserData, ui_state: &UiState) {
         if self.hidden {
@@ -241,9 +594,42 @@ impl button::Button {
         use button::{Id::*, Kind::*};
         match self.kind {
             RectWithText => {
+                let editing = match self.id {
+                    CurrentActivity => matches!(
+                        ui_state.imode,
+                        InteractMode::ActivityRename | InteractMode::ActivitySearch
+                    ),
+                    ActivityGroup => matches!(ui_state.imode, InteractMode::GroupRename),
+                    Goals => matches!(ui_state.imode, InteractMode::GoalsEdit),
+                    _ => false,
+                };
+                if editing {
+                    draw_text_field(
+                        render_ctx,
+                        self.rect,
+                        &ui_state.rename_field,
+                        self.highlighted,
+                    );
+                    return;
+                }
+                let label;
                 let string = match self.id {
-                    CurrentActivity => {
-                        &user_data.activities[ui_state.current_activity as usize].name
+                    CurrentActivity => &user_data.activities[ui_state.current_activity].name,
+                    GroupFilter => {
+                        label = match &ui_state.group {
+                            Some(group) => format!("Group: {group}"),
+                            None => "Group: all".to_owned(),
+                        };
+                        &label
+                    }
+                    ActivityGroup => {
+                        let group = &user_data.activities[ui_state.current_activity].group;
+                        if group.is_empty() {
+                            "Set group"
+                        } else {
+                            label = format!("In group: {group}");
+                            &label
+                        }
                     }
                     Overview => {
                         if ui_state.overview {
@@ -260,6 +646,43 @@ impl button::Button {
                         }
                     }
                     EditMode => "Edit mode",
+                    Statistics => {
+                        if ui_state.stats_view {
+                            "Calendar"
+                        } else {
+                            "Statistics"
+                        }
+                    }
+                    Goals => "Edit goals",
+                    MergeSaveFile => "Merge save file",
+                    Polarity => {
+                        if user_data.activities[ui_state.current_activity].is_negative() {
+                            "Habit: quit"
+                        } else {
+                            "Habit: build"
+                        }
+                    }
+                    Achievements => {
+                        if ui_state.achievements_view {
+                            "Calendar"
+                        } else {
+                            "Achievements"
+                        }
+                    }
+                    InOverview => {
+                        if user_data.activities[ui_state.current_activity].in_overview {
+                            "In overview: yes"
+                        } else {
+                            "In overview: no"
+                        }
+                    }
+                    StreakMode => {
+                        if ui_state.all_habits_streak {
+                            "Streak: all habits"
+                        } else {
+                            "Streak: any habit"
+                        }
+                    }
                     _ => panic!("Unknown text button"),
                 };
                 draw_rect_with_text(
@@ -293,7 +716,13 @@ impl button::Button {
 }
 
 impl SideUi {
-    pub fn draw(&self, render_ctx: &mut RenderContext, user_data: &UserData, ui_state: &UiState) {
+    pub fn draw(
+        &self,
+        render_ctx: &mut RenderContext,
+        current_date: NaiveDate,
+        user_data: &UserData,
+        ui_state: &UiState,
+    ) {
         for button in &self.buttons {
             button.draw(render_ctx, user_data, ui_state);
         }
@@ -301,29 +730,109 @@ impl SideUi {
         //let longest_streak_txt = format!("Longest streak:", Fancy(ui_state.longest_streak));
         let mut rs = RectangleShape::new();
         rs.set_fill_color(Color::rgba(0, 0, 0, 180));
-        rs.set_position((904., 300.));
+        rs.set_position((904., 334.));
         rs.set_size((180.0, 100.0));
         render_ctx.rw.draw(&rs);
         render_ctx.text.set_fill_color(Color::rgb(255, 255, 255));
-        render_ctx.text.set_position((908., 300.));
+        render_ctx.text.set_position((908., 334.));
         render_ctx.text.set_string("Current streak:");
         render_ctx.rw.draw(&render_ctx.text);
-        render_ctx.text.set_position((908., 320.));
+        render_ctx.text.set_position((908., 354.));
         render_ctx
             .text
             .set_string(&format!("{}", Fancy(ui_state.current_streak)));
         render_ctx.rw.draw(&render_ctx.text);
-        render_ctx.text.set_position((908., 340.));
+        render_ctx.text.set_position((908., 374.));
         render_ctx.text.set_string("Longest streak:");
         render_ctx.rw.draw(&render_ctx.text);
-        render_ctx.text.set_position((908., 360.));
+        render_ctx.text.set_position((908., 394.));
         render_ctx
             .text
             .set_string(&format!("{}", Fancy(ui_state.longest_streak)));
         render_ctx.rw.draw(&render_ctx.text);
+        if !ui_state.overview {
+            let freezes = &user_data.activities[ui_state.current_activity].freezes;
+            render_ctx.text.set_position((908., 414.));
+            render_ctx
+                .text
+                .set_string(&format!("Freezes: {}", freezes.available));
+            render_ctx.rw.draw(&render_ctx.text);
+            draw_goals(
+                render_ctx,
+                current_date,
+                &user_data.activities[ui_state.current_activity],
+            );
+        }
     }
 }
 
+/// Progress of each goal of `activity`, below the goals button
+fn draw_goals(render_ctx: &mut RenderContext, current_date: NaiveDate, activity: &Activity) {
+    const ROW_HEIGHT: f32 = 26.0;
+    let top = 444.0 + 4.0 * 42.0;
+    let mut rs = RectangleShape::new();
+    for (i, goal) in activity.goals.iter().enumerate() {
+        let y = top + i as f32 * ROW_HEIGHT;
+        if y + ROW_HEIGHT > f32::from(RES.1) {
+            break;
+        }
+        let (progress, target) = goal.progress(activity, current_date);
+        let fraction = if target == 0 {
+            1.0
+        } else {
+            (progress as f32 / target as f32).min(1.0)
+        };
+        rs.set_fill_color(Color::rgba(0, 0, 0, 180));
+        rs.set_position((904., y));
+        rs.set_size((180.0, ROW_HEIGHT - 4.0));
+        render_ctx.rw.draw(&rs);
+        rs.set_fill_color(render_ctx.palette.accent);
+        rs.set_position((904., y + ROW_HEIGHT - 8.0));
+        rs.set_size((180.0 * fraction, 4.0));
+        render_ctx.rw.draw(&rs);
+        render_ctx.text.set_fill_color(if fraction >= 1.0 {
+            render_ctx.palette.accent_bright
+        } else {
+            Color::WHITE
+        });
+        render_ctx.text.set_character_size(13);
+        draw_text(
+            render_ctx,
+            908,
+            y as i16 + 2,
+            &format!("{} {progress}/{target}", goal.describe()),
+        );
+        render_ctx.text.set_character_size(16);
+    }
+}
+
+/// Banner over the calendar, fading out towards the end of the celebration
+pub(super) fn draw_celebration(render_ctx: &mut RenderContext, celebration: &Celebration) {
+    let remaining =
+        1.0 - celebration.start.elapsed().as_secs_f32() / CELEBRATION_DURATION.as_secs_f32();
+    let alpha = (remaining.clamp(0.0, 1.0) * 2.0).min(1.0);
+    let (x, y, w, h) = (0.0, f32::from(RES.1) / 2.0 - 40.0, 900.0, 80.0);
+    let mut rs = RectangleShape::new();
+    let accent = render_ctx.palette.accent;
+    rs.set_fill_color(Color::rgba(
+        accent.r,
+        accent.g,
+        accent.b,
+        (alpha * 230.0) as u8,
+    ));
+    rs.set_outline_color(Color::rgba(255, 255, 255, (alpha * 255.0) as u8));
+    rs.set_outline_thickness(3.0);
+    rs.set_position((x, y));
+    rs.set_size((w, h));
+    render_ctx.rw.draw(&rs);
+    render_ctx.text.set_character_size(28);
+    render_ctx
+        .text
+        .set_fill_color(Color::rgba(255, 255, 255, (alpha * 255.0) as u8));
+    draw_text_wrapped(render_ctx, &celebration.message, x, y, w, h);
+    render_ctx.text.set_character_size(16);
+}
+
 struct Fancy(u32);
 
 impl std::fmt::Display for Fancy {
@@ -0,0 +1,211 @@
+use sfml::window::{clipboard, Event, Key};
+use std::ops::Range;
+
+/// Single line text input with a cursor, selection and clipboard support.
+///
+/// Positions are byte offsets into the text, always on char boundaries.
+pub struct TextField {
+    text: String,
+    original: String,
+    cursor: usize,
+    /// The other end of the selection, if any
+    anchor: Option<usize>,
+    /// Maximum length of the text in bytes
+    max_len: usize,
+}
+
+/// What the owner of the field should do after an event
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Editing,
+    Commit,
+    Cancel,
+}
+
+impl TextField {
+    pub fn new(text: &str, max_len: usize) -> Self {
+        let mut field = Self {
+            text: String::new(),
+            original: text.to_owned(),
+            cursor: 0,
+            anchor: None,
+            max_len,
+        };
+        field.insert_str(text);
+        field.select_all();
+        field
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// The text the field was created with, for reverting
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+    /// Cursor position in chars, for rendering
+    pub fn cursor_char_idx(&self) -> usize {
+        self.text[..self.cursor].chars().count()
+    }
+    /// Selected range in chars, for rendering
+    pub fn selection_char_range(&self) -> Option<Range<usize>> {
+        self.selection()
+            .map(|r| self.text[..r.start].chars().count()..self.text[..r.end].chars().count())
+    }
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        if anchor == self.cursor {
+            None
+        } else {
+            Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+        }
+    }
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(range) => {
+                self.cursor = range.start;
+                self.text.replace_range(range, "");
+                self.anchor = None;
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+    pub fn insert_str(&mut self, s: &str) {
+        self.delete_selection();
+        for c in s.chars().filter(|c| !c.is_control()) {
+            if self.text.len() + c.len_utf8() > self.max_len {
+                break;
+            }
+            self.text.insert(self.cursor, c);
+            self.cursor += c.len_utf8();
+        }
+    }
+    pub fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if let Some(c) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+    pub fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+    fn move_to(&mut self, pos: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = pos;
+    }
+    pub fn left(&mut self, select: bool) {
+        let pos = match (self.selection(), select) {
+            (Some(range), false) => range.start,
+            _ => self.text[..self.cursor]
+                .chars()
+                .next_back()
+                .map_or(self.cursor, |c| self.cursor - c.len_utf8()),
+        };
+        self.move_to(pos, select);
+    }
+    pub fn right(&mut self, select: bool) {
+        let pos = match (self.selection(), select) {
+            (Some(range), false) => range.end,
+            _ => self.text[self.cursor..]
+                .chars()
+                .next()
+                .map_or(self.cursor, |c| self.cursor + c.len_utf8()),
+        };
+        self.move_to(pos, select);
+    }
+    pub fn home(&mut self, select: bool) {
+        self.move_to(0, select);
+    }
+    pub fn end(&mut self, select: bool) {
+        self.move_to(self.text.len(), select);
+    }
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+    fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|r| &self.text[r])
+    }
+    pub fn handle_event(&mut self, event: &Event) -> Outcome {
+        match *event {
+            Event::TextEntered { unicode } => match unicode {
+                '\u{8}' => self.backspace(),
+                '\r' | '\n' => return Outcome::Commit,
+                c if c.is_control() => {}
+                c => self.insert_str(c.encode_utf8(&mut [0; 4])),
+            },
+            Event::KeyPressed {
+                code, ctrl, shift, ..
+            } => match code {
+                Key::Escape => return Outcome::Cancel,
+                Key::Left => self.left(shift),
+                Key::Right => self.right(shift),
+                Key::Home => self.home(shift),
+                Key::End => self.end(shift),
+                Key::Delete => self.delete(),
+                Key::A if ctrl => self.select_all(),
+                Key::C if ctrl => {
+                    if let Some(sel) = self.selected_text() {
+                        clipboard::set_string(sel);
+                    }
+                }
+                Key::X if ctrl => {
+                    if let Some(sel) = self.selected_text() {
+                        clipboard::set_string(sel);
+                        self.delete_selection();
+                    }
+                }
+                Key::V if ctrl => self.insert_str(&clipboard::get_string().to_string()),
+                _ => {}
+            },
+            _ => {}
+        }
+        Outcome::Editing
+    }
+}
+
+#[test]
+fn test_text_field_editing() {
+    let mut field = TextField::new("Run", 32);
+    // Everything is selected initially, so typing replaces it
+    field.insert_str("Walk");
+    assert_eq!(field.text(), "Walk");
+    field.left(false);
+    field.left(false);
+    field.insert_str("\tx");
+    assert_eq!(field.text(), "Waxlk");
+    field.backspace();
+    field.delete();
+    assert_eq!(field.text(), "Wak");
+    field.home(false);
+    field.right(true);
+    field.right(true);
+    assert_eq!(field.selection_char_range(), Some(0..2));
+    field.backspace();
+    assert_eq!(field.text(), "k");
+    assert_eq!(field.original(), "Run");
+}
+
+#[test]
+fn test_text_field_max_len() {
+    let mut field = TextField::new("", 4);
+    field.insert_str("abcdef");
+    assert_eq!(field.text(), "abcd");
+    // Multi-byte chars must not be split at the limit
+    let mut field = TextField::new("", 4);
+    field.insert_str("aéé");
+    assert_eq!(field.text(), "aé");
+    assert_eq!(field.cursor_char_idx(), 2);
+}
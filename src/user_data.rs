@@ -1,36 +1,244 @@
+use crate::{
+    achievements::Achievements,
+    goals::Goal,
+    stats::{self, Freezes, HasDate},
+};
 use chrono::prelude::*;
-use std::collections::HashSet;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::BTreeSet, fmt, num::ParseIntError, ops::RangeInclusive, str::FromStr};
 
+mod json;
+mod merge;
 mod ser;
 
+pub use merge::{Conflict, MergeReport};
+pub use ser::{can_store_date, Corruption, LoadError};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UserData {
     pub activities: Vec<Activity>,
+    pub achievements: Achievements,
 }
 
 impl UserData {
-    fn new_default(current_date: NaiveDate) -> Self {
+    pub fn new_default(current_date: NaiveDate) -> Self {
         Self {
             activities: vec![Activity::new_default(current_date)],
+            achievements: Achievements::default(),
         }
     }
+    /// The activities that are aggregated in overview mode, optionally limited to a group
+    pub fn overview_activities<'a>(
+        &'a self,
+        group: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Activity> {
+        self.activities
+            .iter()
+            .filter(move |a| a.counts_in_overview(group))
+    }
+    /// Earliest starting date of the activities in the overview
+    pub fn overview_start(&self, group: Option<&str>) -> Option<NaiveDate> {
+        self.overview_activities(group)
+            .map(|a| a.starting_date)
+            .min()
+    }
+    /// Names of all the groups, sorted
+    pub fn groups(&self) -> BTreeSet<&str> {
+        self.activities
+            .iter()
+            .filter(|a| !a.group.is_empty())
+            .map(|a| a.group.as_str())
+            .collect()
+    }
+    /// Index of the activity with `id`
+    pub fn activity_index(&self, id: ActivityId) -> Option<usize> {
+        self.activities.iter().position(|a| a.id == id)
+    }
     pub fn insert_default_activity(&mut self, index: usize, current_date: NaiveDate) {
         self.activities
             .insert(index, Activity::new_default(current_date));
     }
 }
 
+/// Maximum length of an activity or group name in bytes, as the save format stores it in an u8
+pub const MAX_NAME_LEN: usize = 255;
+
+/// Identifies an activity across renames, reorderings and machines.
+///
+/// Written as 16 hexadecimal digits, also in JSON, where numbers this large lose precision
+/// in many programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ActivityId(pub u64);
+
+impl ActivityId {
+    /// A new random ID, so IDs made on different machines don't collide
+    pub fn generate() -> Self {
+        let mut bytes = [0; 8];
+        // Only fails on platforms without any source of randomness
+        getrandom::getrandom(&mut bytes).expect("Can't get random bytes for an activity ID");
+        Self(u64::from_le_bytes(bytes))
+    }
+}
+
+impl fmt::Display for ActivityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for ActivityId {
+    type Err = ParseIntError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(Self)
+    }
+}
+
+impl Serialize for ActivityId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ActivityId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdVisitor;
+        impl Visitor<'_> for IdVisitor {
+            type Value = ActivityId;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an activity ID in hexadecimal")
+            }
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ActivityId, E> {
+                v.parse()
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+            // Earlier exports wrote IDs as numbers
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<ActivityId, E> {
+                Ok(ActivityId(v))
+            }
+        }
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Activity {
+    /// Exports from before IDs existed get new ones
+    #[serde(default = "ActivityId::generate")]
+    pub id: ActivityId,
     pub name: String,
     pub starting_date: NaiveDate,
-    pub dates: HashSet<NaiveDate>,
+    pub dates: BTreeSet<NaiveDate>,
+    /// Whether this activity is aggregated in overview mode
+    pub in_overview: bool,
+    /// Name of the group this activity belongs to, empty if it's not in a group
+    pub group: String,
+    pub goals: Vec<Goal>,
+    pub freezes: Freezes,
+    pub polarity: Polarity,
+}
+
+/// What checking a day of an activity means
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Polarity {
+    /// The habit was done, like "exercise"
+    Positive,
+    /// The habit relapsed, like "smoke". Streaks are the days between relapses.
+    Negative,
 }
 
 impl Activity {
-    fn new_default(current_date: NaiveDate) -> Self {
+    pub fn new_default(current_date: NaiveDate) -> Self {
         Self {
+            id: ActivityId::generate(),
             name: "New Unnamed Activity".to_owned(),
-            dates: HashSet::default(),
+            dates: BTreeSet::default(),
             starting_date: current_date,
+            in_overview: true,
+            group: String::new(),
+            goals: Vec::new(),
+            freezes: Freezes::default(),
+            polarity: Polarity::Positive,
+        }
+    }
+    pub fn is_negative(&self) -> bool {
+        self.polarity == Polarity::Negative
+    }
+    /// Recompute the streak freezes earned and used up to `current_date`.
+    ///
+    /// Negative habits don't have freezes.
+    pub fn update_freezes(&mut self, current_date: NaiveDate) {
+        self.freezes = if self.is_negative() {
+            Freezes::default()
+        } else {
+            stats::compute_freezes(&self.dates, self.starting_date, current_date)
+        };
+    }
+    /// Current streak, counting freezes, or the days since the last relapse of negative habits
+    pub fn current_streak(&self, current_date: NaiveDate) -> u32 {
+        if self.is_negative() {
+            stats::find_current_gap(&self.dates, self.starting_date, current_date)
+        } else {
+            stats::find_current_streak(&self.streak_dates(), current_date)
+        }
+    }
+    /// Longest streak, or the longest time between relapses of negative habits
+    pub fn longest_streak(&self, current_date: NaiveDate) -> u32 {
+        if self.is_negative() {
+            stats::find_longest_gap(&self.dates, self.starting_date, current_date)
+        } else {
+            stats::find_longest_streak(self.starting_date, &self.streak_dates(), current_date)
+        }
+    }
+    /// How many days of `range` were done. For negative habits, those are the days
+    /// without a relapse after the starting date.
+    pub fn done_in(&self, range: RangeInclusive<NaiveDate>) -> usize {
+        let marked = self.dates.dates_in(range.clone()).count();
+        if !self.is_negative() {
+            return marked;
+        }
+        let start = (*range.start()).max(self.starting_date);
+        if start > *range.end() {
+            return 0;
+        }
+        (*range.end() - start).num_days() as usize + 1
+            - self.dates.dates_in(start..=*range.end()).count()
+    }
+    /// Days without a relapse of a negative habit, from the starting date to `current_date`
+    pub fn clean_days(&self, current_date: NaiveDate) -> BTreeSet<NaiveDate> {
+        self.starting_date
+            .iter_days()
+            .take_while(|&date| date <= current_date)
+            .filter(|date| !self.dates.contains(date))
+            .collect()
+    }
+    /// Done days, and the missed days covered by a freeze
+    pub fn streak_dates(&self) -> impl HasDate + '_ {
+        stats::Union(&self.dates, &self.freezes.covered)
+    }
+    pub fn in_group(&self, group: Option<&str>) -> bool {
+        match group {
+            Some(group) => self.group == group,
+            None => true,
+        }
+    }
+    /// Whether this activity is aggregated in the overview of `group`.
+    ///
+    /// Negative habits never are, as their checked days mean the opposite.
+    pub fn counts_in_overview(&self, group: Option<&str>) -> bool {
+        self.in_overview && !self.is_negative() && self.in_group(group)
+    }
+    /// Check `date` if it's unchecked, uncheck it otherwise.
+    ///
+    /// Returns whether the date is checked now.
+    pub fn toggle_date(&mut self, date: NaiveDate) -> bool {
+        if self.dates.insert(date) {
+            true
+        } else {
+            self.dates.remove(&date);
+            false
         }
     }
 }
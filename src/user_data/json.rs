@@ -0,0 +1,102 @@
+use super::{LoadError, UserData, MAX_NAME_LEN};
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    io::{Read, Write},
+};
+
+impl UserData {
+    /// Writes all the data as JSON, to be read by other programs or imported again
+    pub fn export_json<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+    /// Reads data written by `export_json`
+    pub fn import_json<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        let data: Self = serde_json::from_reader(reader)?;
+        if data.activities.is_empty() {
+            return Err("There are no activities".into());
+        }
+        let mut ids = BTreeSet::new();
+        for activity in &data.activities {
+            if !ids.insert(activity.id) {
+                return Err(format!("Duplicate activity id {}", activity.id).into());
+            }
+            // The save format can't store anything longer
+            for name in [&activity.name, &activity.group] {
+                if name.len() > MAX_NAME_LEN {
+                    return Err(format!("Name is longer than {MAX_NAME_LEN} bytes: {name}").into());
+                }
+            }
+            if activity.goals.len() > usize::from(u8::MAX) {
+                return Err(format!("Too many goals for {}", activity.name).into());
+            }
+        }
+        // Anything else the save file couldn't be loaded with afterwards, like dates out of
+        // range or too many freezes
+        let mut buf = Vec::new();
+        data.write_to(&mut buf)?;
+        match Self::read_from(&buf[..]) {
+            Ok(_) => Ok(data),
+            Err(LoadError::Corrupt { kind, .. }) => Err(format!("Invalid data: {kind}").into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[test]
+fn test_json_roundtrip() {
+    use crate::{
+        goals::Goal,
+        user_data::{ActivityId, Polarity},
+    };
+    use chrono::NaiveDate;
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut data = UserData::new_default(date);
+    data.activities[0].dates.insert(date);
+    data.activities[0].goals.push(Goal::MonthlyCompletion(80));
+    data.activities[0].polarity = Polarity::Negative;
+    data.activities[0].group = "Health".to_owned();
+    let mut json = Vec::new();
+    data.export_json(&mut json).unwrap();
+    // IDs are strings, as many JSON readers can't hold every u64 in a number
+    let id = format!("\"id\": \"{}\"", data.activities[0].id);
+    assert!(String::from_utf8_lossy(&json).contains(&id));
+    let imported = UserData::import_json(&json[..]).unwrap();
+    let activity = &imported.activities[0];
+    assert_eq!(activity.id, data.activities[0].id);
+    assert_eq!(activity.dates, data.activities[0].dates);
+    assert_eq!(activity.goals, [Goal::MonthlyCompletion(80)]);
+    assert_eq!(activity.polarity, Polarity::Negative);
+    assert_eq!(activity.group, "Health");
+    let reexport = |data: &UserData| {
+        let mut json = Vec::new();
+        data.export_json(&mut json).unwrap();
+        UserData::import_json(&json[..])
+    };
+    data.activities
+        .push(crate::user_data::Activity::new_default(date));
+    data.activities[1].id = data.activities[0].id;
+    assert!(reexport(&data).is_err());
+    data.activities.pop();
+    data.activities[0].name = "a".repeat(MAX_NAME_LEN + 1);
+    assert!(reexport(&data).is_err());
+    data.activities.clear();
+    assert!(reexport(&data).is_err());
+    // Earlier exports wrote IDs as numbers
+    let json = r#"{"activities": [{"id": 18446744073709551615, "name": "Run",
+        "starting_date": "2023-01-31", "dates": [], "in_overview": true, "group": "",
+        "goals": [], "freezes": {"available": 0, "covered": []}, "polarity": "Positive"}],
+        "achievements": {"earned": {}}}"#;
+    let imported = UserData::import_json(json.as_bytes()).unwrap();
+    assert_eq!(imported.activities[0].id, ActivityId(u64::MAX));
+    // Values the save file can't hold are refused, so they can't make it unloadable
+    for (valid, invalid) in [
+        (r#""available": 0"#, r#""available": 9"#),
+        (r#""2023-01-31""#, r#""-0001-01-31""#),
+        (r#""goals": []"#, r#""goals": [{"MonthlyCompletion": 101}]"#),
+    ] {
+        let json = json.replace(valid, invalid);
+        assert!(UserData::import_json(json.as_bytes()).is_err(), "{json}");
+    }
+}
@@ -0,0 +1,450 @@
+use super::{Activity, UserData};
+use crate::achievements::Achievements;
+use std::fmt;
+
+/// A difference between two matched activities that can't be combined.
+///
+/// The value of the data that is merged into is kept.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Conflict {
+    Group {
+        activity: String,
+        kept: String,
+        other: String,
+    },
+    Goals {
+        activity: String,
+    },
+    Polarity {
+        activity: String,
+    },
+    /// The same activity has a different name in the other data
+    Renamed {
+        kept: String,
+        other: String,
+    },
+    /// Activities that are only in one of the files, but started on the same day.
+    /// One of them might have been renamed.
+    PossibleRename {
+        here: String,
+        other: String,
+    },
+    /// Changed here, but deleted in the other data
+    DeletedThere {
+        activity: String,
+    },
+    /// Deleted here, but changed in the other data
+    DeletedHere {
+        activity: String,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Group {
+                activity,
+                kept,
+                other,
+            } => write!(
+                f,
+                "{activity}: kept group \"{kept}\", the other file has \"{other}\""
+            ),
+            Self::Goals { activity } => {
+                write!(
+                    f,
+                    "{activity}: kept goals, the other file has different ones"
+                )
+            }
+            Self::Polarity { activity } => write!(
+                f,
+                "{activity}: kept habit type, the other file has the opposite"
+            ),
+            Self::Renamed { kept, other } => {
+                write!(f, "kept name \"{kept}\", the other file has \"{other}\"")
+            }
+            Self::PossibleRename { here, other } => {
+                write!(f, "\"{here}\" might have been renamed to \"{other}\"")
+            }
+            Self::DeletedThere { activity } => {
+                write!(f, "{activity}: kept changes, the other file deleted it")
+            }
+            Self::DeletedHere { activity } => {
+                write!(f, "{activity}: kept deleted, the other file changed it")
+            }
+        }
+    }
+}
+
+/// What `UserData::merge` or `UserData::merge_since` changed
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Activities that were only in the other data, and got added
+    pub added: Vec<String>,
+    /// Activities that are only in this data, either new here or deleted in the other data.
+    /// Only known without a common base.
+    pub only_here: Vec<String>,
+    /// Activities that were deleted in the other data, and got removed
+    pub removed: Vec<String>,
+    /// Checked days that were only in the other data
+    pub new_days: usize,
+    /// Days that were unchecked in the other data
+    pub removed_days: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} new checked days", self.new_days)?;
+        if self.removed_days != 0 {
+            writeln!(f, "{} unchecked days", self.removed_days)?;
+        }
+        if !self.added.is_empty() {
+            writeln!(f, "Added activities: {}", self.added.join(", "))?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Removed activities: {}", self.removed.join(", "))?;
+        }
+        if !self.only_here.is_empty() {
+            writeln!(
+                f,
+                "Not in the other file, kept: {}",
+                self.only_here.join(", ")
+            )?;
+        }
+        for conflict in &self.conflicts {
+            writeln!(f, "Conflict: {conflict}")?;
+        }
+        Ok(())
+    }
+}
+
+impl UserData {
+    /// Merges `other`, like the save file of another machine, into this data.
+    ///
+    /// Activities are matched by ID, or by name if the IDs differ, like for activities
+    /// that got their IDs on different machines. Their checked days are combined.
+    /// Freezes are not merged, call `Activity::update_freezes` afterwards.
+    pub fn merge(&mut self, other: UserData) -> MergeReport {
+        let mut report = MergeReport::default();
+        let n_here = self.activities.len();
+        let mut matched = vec![false; n_here];
+        let mut added = Vec::new();
+        let (by_id, rest): (Vec<_>, Vec<_>) = other
+            .activities
+            .into_iter()
+            .partition(|a| self.activity_index(a.id).is_some());
+        for theirs in by_id {
+            let idx = self.activity_index(theirs.id).unwrap();
+            matched[idx] = true;
+            if self.activities[idx].name != theirs.name {
+                report.conflicts.push(Conflict::Renamed {
+                    kept: self.activities[idx].name.clone(),
+                    other: theirs.name.clone(),
+                });
+            }
+            merge_activity(&mut self.activities[idx], theirs, &mut report);
+        }
+        for theirs in rest {
+            let found =
+                (0..n_here).find(|&i| !matched[i] && self.activities[i].name == theirs.name);
+            match found {
+                Some(idx) => {
+                    matched[idx] = true;
+                    merge_activity(&mut self.activities[idx], theirs, &mut report);
+                }
+                None => {
+                    report.added.push(theirs.name.clone());
+                    added.push(self.activities.len());
+                    self.activities.push(theirs);
+                }
+            }
+        }
+        for (idx, _) in matched.iter().enumerate().filter(|(_, &m)| !m) {
+            let here = &self.activities[idx];
+            report.only_here.push(here.name.clone());
+            if let Some(&renamed) = added
+                .iter()
+                .find(|&&i| self.activities[i].starting_date == here.starting_date)
+            {
+                report.conflicts.push(Conflict::PossibleRename {
+                    here: here.name.clone(),
+                    other: self.activities[renamed].name.clone(),
+                });
+            }
+        }
+        self.merge_achievements(other.achievements);
+        report
+    }
+    /// Merges the changes made in `other` since `base`, the version both started from,
+    /// into this data.
+    ///
+    /// Unlike `merge`, this also applies unchecked days, deleted activities and changed
+    /// fields of the other data. Activities are matched by ID only, and there is only a
+    /// conflict if both sides changed the same thing. Freezes are not merged, call
+    /// `Activity::update_freezes` afterwards.
+    pub fn merge_since(&mut self, base: &UserData, other: UserData) -> MergeReport {
+        let mut report = MergeReport::default();
+        let mut theirs = other.activities;
+        let mut deleted = Vec::new();
+        for (idx, here) in self.activities.iter_mut().enumerate() {
+            let in_base = base.activities.iter().find(|a| a.id == here.id);
+            match (theirs.iter().position(|a| a.id == here.id), in_base) {
+                (Some(pos), Some(in_base)) => {
+                    merge_activity_since(here, in_base, theirs.remove(pos), &mut report)
+                }
+                // Added on both sides, like when there was no file yet
+                (Some(pos), None) => merge_activity(here, theirs.remove(pos), &mut report),
+                (None, Some(in_base)) if changed_since(in_base, here) => {
+                    report.conflicts.push(Conflict::DeletedThere {
+                        activity: here.name.clone(),
+                    })
+                }
+                (None, Some(_)) => deleted.push(idx),
+                // New here
+                (None, None) => {}
+            }
+        }
+        for theirs in theirs {
+            match base.activities.iter().find(|a| a.id == theirs.id) {
+                Some(in_base) => {
+                    if changed_since(in_base, &theirs) {
+                        report.conflicts.push(Conflict::DeletedHere {
+                            activity: theirs.name,
+                        });
+                    }
+                }
+                None => {
+                    report.added.push(theirs.name.clone());
+                    self.activities.push(theirs);
+                }
+            }
+        }
+        // Keep one activity if all got deleted on one side or the other, there has to be
+        // something to show
+        if deleted.len() == self.activities.len() {
+            deleted.pop();
+        }
+        for idx in deleted.into_iter().rev() {
+            report.removed.push(self.activities.remove(idx).name);
+        }
+        self.merge_achievements(other.achievements);
+        report
+    }
+    /// Badges are never taken away, so they are combined, keeping the earliest dates
+    fn merge_achievements(&mut self, other: Achievements) {
+        for (badge, date) in other.earned {
+            let earned = self.achievements.earned.entry(badge).or_insert(date);
+            *earned = (*earned).min(date);
+        }
+    }
+}
+
+/// Whether the user changed `now` since `base`. Freezes are computed, so they don't count.
+fn changed_since(base: &Activity, now: &Activity) -> bool {
+    base.name != now.name
+        || base.starting_date != now.starting_date
+        || base.dates != now.dates
+        || base.in_overview != now.in_overview
+        || base.group != now.group
+        || base.goals != now.goals
+        || base.polarity != now.polarity
+}
+
+/// Takes the value of `theirs` if only they changed it since `base`.
+///
+/// Returns their value if both changed it differently, `here` is kept then.
+fn merge_field<T: PartialEq>(here: &mut T, base: &T, theirs: T) -> Option<T> {
+    if *here == *base {
+        *here = theirs;
+        None
+    } else if theirs == *base || *here == theirs {
+        None
+    } else {
+        Some(theirs)
+    }
+}
+
+fn merge_activity_since(
+    here: &mut Activity,
+    base: &Activity,
+    theirs: Activity,
+    report: &mut MergeReport,
+) {
+    for date in theirs.dates.difference(&base.dates) {
+        if here.dates.insert(*date) {
+            report.new_days += 1;
+        }
+    }
+    for date in base.dates.difference(&theirs.dates) {
+        if here.dates.remove(date) {
+            report.removed_days += 1;
+        }
+    }
+    if let Some(other) = merge_field(&mut here.name, &base.name, theirs.name) {
+        report.conflicts.push(Conflict::Renamed {
+            kept: here.name.clone(),
+            other,
+        });
+    }
+    // Both moved it, the earlier date keeps the days of both in the tracked period
+    if let Some(other) = merge_field(
+        &mut here.starting_date,
+        &base.starting_date,
+        theirs.starting_date,
+    ) {
+        here.starting_date = here.starting_date.min(other);
+    }
+    // A flag can't be changed differently by both
+    merge_field(&mut here.in_overview, &base.in_overview, theirs.in_overview);
+    if let Some(other) = merge_field(&mut here.group, &base.group, theirs.group) {
+        report.conflicts.push(Conflict::Group {
+            activity: here.name.clone(),
+            kept: here.group.clone(),
+            other,
+        });
+    }
+    if merge_field(&mut here.goals, &base.goals, theirs.goals).is_some() {
+        report.conflicts.push(Conflict::Goals {
+            activity: here.name.clone(),
+        });
+    }
+    if merge_field(&mut here.polarity, &base.polarity, theirs.polarity).is_some() {
+        report.conflicts.push(Conflict::Polarity {
+            activity: here.name.clone(),
+        });
+    }
+}
+
+fn merge_activity(here: &mut Activity, theirs: Activity, report: &mut MergeReport) {
+    let n_before = here.dates.len();
+    here.dates.extend(theirs.dates);
+    report.new_days += here.dates.len() - n_before;
+    here.starting_date = here.starting_date.min(theirs.starting_date);
+    if here.group != theirs.group {
+        report.conflicts.push(Conflict::Group {
+            activity: here.name.clone(),
+            kept: here.group.clone(),
+            other: theirs.group,
+        });
+    }
+    if here.goals != theirs.goals {
+        report.conflicts.push(Conflict::Goals {
+            activity: here.name.clone(),
+        });
+    }
+    if here.polarity != theirs.polarity {
+        report.conflicts.push(Conflict::Polarity {
+            activity: here.name.clone(),
+        });
+    }
+}
+
+#[test]
+fn test_merge() {
+    use crate::{achievements::Badge, user_data::Polarity};
+    use chrono::NaiveDate;
+    let date = |day| NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+    let activity = |name: &str, start| {
+        let mut activity = Activity::new_default(start);
+        activity.name = name.to_owned();
+        activity
+    };
+    let mut here = UserData::new_default(date(5));
+    here.activities = vec![
+        activity("Run", date(5)),
+        activity("Read", date(5)),
+        activity("Write", date(10)),
+    ];
+    here.activities[0].dates.extend([date(5), date(6)]);
+    here.achievements.earned.insert(Badge::FirstWeek, date(20));
+    let mut there = UserData::new_default(date(1));
+    // Different IDs, like when both machines upgraded from a version without IDs
+    there.activities = vec![
+        activity("Run", date(1)),
+        activity("Reading", date(5)),
+        activity("Swim", date(10)),
+    ];
+    there.activities[0].dates.extend([date(6), date(7)]);
+    there.activities[0].group = "Sport".to_owned();
+    there.activities[0].polarity = Polarity::Negative;
+    there.activities[1].id = here.activities[1].id;
+    there.activities[1].dates.insert(date(8));
+    there.achievements.earned.insert(Badge::FirstWeek, date(15));
+    let report = here.merge(there);
+    let run = &here.activities[0];
+    assert_eq!(run.dates.len(), 3);
+    assert_eq!(run.starting_date, date(1));
+    assert_eq!(run.group, "");
+    assert_eq!(run.polarity, Polarity::Positive);
+    assert_eq!(here.activities[1].name, "Read");
+    assert_eq!(here.activities[1].dates.len(), 1);
+    assert_eq!(report.new_days, 2);
+    assert_eq!(report.added, ["Swim"]);
+    assert_eq!(report.only_here, ["Write"]);
+    assert_eq!(
+        report.conflicts,
+        [
+            Conflict::Renamed {
+                kept: "Read".to_owned(),
+                other: "Reading".to_owned()
+            },
+            Conflict::Group {
+                activity: "Run".to_owned(),
+                kept: String::new(),
+                other: "Sport".to_owned()
+            },
+            Conflict::Polarity {
+                activity: "Run".to_owned()
+            },
+            Conflict::PossibleRename {
+                here: "Write".to_owned(),
+                other: "Swim".to_owned()
+            },
+        ]
+    );
+    assert_eq!(here.activities.len(), 4);
+    assert_eq!(here.achievements.earned[&Badge::FirstWeek], date(15));
+}
+
+#[test]
+fn test_merge_since() {
+    use chrono::NaiveDate;
+    let date = |day| NaiveDate::from_ymd_opt(2023, 3, day).unwrap();
+    let mut base = UserData::new_default(date(1));
+    base.insert_default_activity(1, date(1));
+    base.insert_default_activity(2, date(1));
+    base.activities[0].dates.extend([date(1), date(2)]);
+    let mut here = base.clone();
+    let mut there = base.clone();
+    // Both renamed, only the other side changed the group
+    here.activities[0].name = "Run".to_owned();
+    there.activities[0].name = "Jog".to_owned();
+    there.activities[0].group = "Sport".to_owned();
+    there.activities[0].dates.remove(&date(1));
+    // Changed here, deleted there
+    here.activities[1].dates.insert(date(3));
+    there.activities.remove(1);
+    // Deleted here, unchanged there
+    here.activities.remove(2);
+    let report = here.merge_since(&base, there);
+    assert_eq!(here.activities.len(), 2);
+    let run = &here.activities[0];
+    assert_eq!(run.name, "Run");
+    assert_eq!(run.group, "Sport");
+    assert_eq!(run.dates.len(), 1);
+    assert_eq!(report.removed_days, 1);
+    assert!(report.removed.is_empty());
+    assert_eq!(
+        report.conflicts,
+        [
+            Conflict::Renamed {
+                kept: "Run".to_owned(),
+                other: "Jog".to_owned()
+            },
+            Conflict::DeletedThere {
+                activity: here.activities[1].name.clone()
+            },
+        ]
+    );
+}
@@ -1,121 +1,549 @@
-use super::{Activity, UserData};
+use super::{Activity, ActivityId, Polarity, UserData, MAX_NAME_LEN};
+use crate::{
+    achievements::{Achievements, Badge},
+    goals::Goal,
+    stats::{Freezes, MAX_FREEZES},
+};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use chrono::prelude::*;
-use rfd::MessageLevel;
 use std::{
-    collections::HashSet,
-    error::Error,
+    collections::BTreeSet,
+    fmt,
     fs::File,
-    io::prelude::*,
+    io::{self, prelude::*, BufReader},
     path::{Path, PathBuf},
 };
 
 const TEST_MODE_PATH: &str = "calen-do-test.dat";
 
-impl UserData {
-    pub fn load_or_new(data_dir: &Path, current_date: NaiveDate, test_mode: bool) -> Self {
-        let path = if test_mode {
-            PathBuf::from(TEST_MODE_PATH)
-        } else {
-            save_path(data_dir)
-        };
-        match Self::try_load(&path) {
-            Ok(data) => data,
-            Err(e) => {
-                let msg = format!(
-                    "Error loading user data from {}: {}\n\
-                    Creating new user data.\n\
-                    If this is your first time running the program, this is natural.",
-                    path.display(),
-                    e
-                );
-                rfd::MessageDialog::new()
-                    .set_title("Warning")
-                    .set_description(&msg)
-                    .set_level(MessageLevel::Info)
-                    .show();
-                Self::new_default(current_date)
+/// Why the save file couldn't be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// There is no save file yet, like on the first run
+    Missing,
+    /// The data is invalid or cut off at byte `offset`
+    Corrupt {
+        offset: u64,
+        kind: Corruption,
+    },
+    /// The save file was written by an incompatible version of the program
+    VersionMismatch {
+        found: u16,
+        supported: u16,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "There is no save file"),
+            Self::Corrupt { offset, kind } => {
+                write!(f, "The save file is corrupt at byte {offset}: {kind}")
             }
+            Self::VersionMismatch { found, supported } => write!(
+                f,
+                "Version mismatch: program supports up to version {supported}, \
+                 save file is version {found}"
+            ),
+            Self::Io(e) => write!(f, "I/O error: {e}"),
         }
     }
-    fn try_load(path: &Path) -> Result<Self, Box<dyn Error>> {
-        let mut f = File::open(path)?;
-        verify(&mut f)?;
-        let n_activities = f.read_u32::<LE>()?;
-        let mut activities = Vec::with_capacity(n_activities as usize);
-        for _ in 0..n_activities {
-            let name_len = f.read_u8()?;
-            let mut name_buf = vec![0; name_len as usize];
-            f.read_exact(&mut name_buf)?;
-            let name = String::from_utf8(name_buf)?;
-            let starting_year = f.read_u16::<LE>()?;
-            let starting_month = f.read_u8()?;
-            let starting_day = f.read_u8()?;
-            let len = f.read_u32::<LE>()?;
-            let mut set = HashSet::with_capacity(len as usize);
-            for _ in 0..len {
-                let year = f.read_u16::<LE>()?;
-                let month = f.read_u8()?;
-                let day = f.read_u8()?;
-                set.insert(NaiveDate::from_ymd_opt(year.into(), month.into(), day.into()).unwrap());
+}
+
+impl std::error::Error for LoadError {}
+
+/// What is wrong with a corrupt save file
+#[derive(Debug, PartialEq, Eq)]
+pub enum Corruption {
+    NotASaveFile,
+    UnexpectedEnd,
+    InvalidUtf8,
+    InvalidDate { year: u16, month: u8, day: u8 },
+    NoActivities,
+    TooManyFreezes(u8),
+    UnknownGoalKind(u8),
+    InvalidPercent(u32),
+    UnknownBadge(u8),
+    DuplicateId(ActivityId),
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::NotASaveFile => write!(f, "not a Calen-Do save file"),
+            Self::UnexpectedEnd => write!(f, "unexpected end of data"),
+            Self::InvalidUtf8 => write!(f, "invalid UTF-8 in name"),
+            Self::InvalidDate { year, month, day } => {
+                write!(f, "invalid date {year}-{month:02}-{day:02}")
             }
-            activities.push(Activity {
-                name,
-                starting_date: NaiveDate::from_ymd_opt(
-                    i32::from(starting_year),
-                    u32::from(starting_month),
-                    u32::from(starting_day),
-                )
-                .unwrap(),
-                dates: set,
-            });
+            Self::NoActivities => write!(f, "no activities"),
+            Self::TooManyFreezes(n) => write!(f, "{n} streak freezes, at most {MAX_FREEZES}"),
+            Self::UnknownGoalKind(kind) => write!(f, "unknown goal kind {kind}"),
+            Self::InvalidPercent(percent) => write!(f, "{percent}% monthly goal, at most 100%"),
+            Self::UnknownBadge(badge) => write!(f, "unknown badge {badge}"),
+            Self::DuplicateId(id) => write!(f, "duplicate activity id {id}"),
         }
-        Ok(UserData { activities })
     }
-    pub fn save(&self, data_dir: &Path, test_mode: bool) -> Result<(), Box<dyn Error>> {
-        let path = if test_mode {
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Keeps track of the position in the data, for error messages
+struct OffsetReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+fn corrupt(offset: u64, kind: Corruption) -> LoadError {
+    LoadError::Corrupt { offset, kind }
+}
+
+/// Reads a string with a u8 length, so it can't be longer than `MAX_NAME_LEN`
+fn read_string<R: Read>(f: &mut OffsetReader<R>) -> Result<String, LoadError> {
+    let offset = f.offset;
+    let len = f.read_u8()?;
+    let mut buf = vec![0; len as usize];
+    f.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| corrupt(offset, Corruption::InvalidUtf8))
+}
+
+fn write_string<W: Write>(f: &mut W, string: &str) -> io::Result<()> {
+    let len = u8::try_from(string.len())
+        .ok()
+        .filter(|&len| usize::from(len) <= MAX_NAME_LEN)
+        .ok_or_else(|| invalid_input(format!("Name is too long: {string}")))?;
+    f.write_u8(len)?;
+    f.write_all(string.as_bytes())
+}
+
+fn read_date<R: Read>(f: &mut OffsetReader<R>) -> Result<NaiveDate, LoadError> {
+    let offset = f.offset;
+    let year = f.read_u16::<LE>()?;
+    let month = f.read_u8()?;
+    let day = f.read_u8()?;
+    NaiveDate::from_ymd_opt(year.into(), month.into(), day.into())
+        .ok_or_else(|| corrupt(offset, Corruption::InvalidDate { year, month, day }))
+}
+
+/// Whether the save format can store `date`, which has to be in a year that fits in a u16
+pub fn can_store_date(date: NaiveDate) -> bool {
+    u16::try_from(date.year()).is_ok()
+}
+
+fn write_date<W: Write>(f: &mut W, date: NaiveDate) -> io::Result<()> {
+    if !can_store_date(date) {
+        return Err(invalid_input(format!("Can't save the date {date}")));
+    }
+    f.write_u16::<LE>(date.year() as u16)?;
+    f.write_u8(date.month() as u8)?;
+    f.write_u8(date.day() as u8)
+}
+
+/// Converts a count to a u8, or fails with an error about `what`
+fn count_u8(len: usize, what: &str) -> io::Result<u8> {
+    u8::try_from(len).map_err(|_| invalid_input(format!("Too many {what}: {len}")))
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl UserData {
+    /// Path of the save file in `data_dir`, or in the working directory in test mode
+    pub fn save_path(data_dir: &Path, test_mode: bool) -> PathBuf {
+        if test_mode {
             PathBuf::from(TEST_MODE_PATH)
         } else {
-            save_path(data_dir)
+            data_dir.join("calen-do.dat")
+        }
+    }
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(LoadError::Missing),
+            Err(e) => return Err(e.into()),
         };
-        let mut f = File::create(path)?;
+        Self::read_from(BufReader::new(file))
+    }
+    /// Reads save data from `reader`, as written by `write_to`
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, LoadError> {
+        let mut f = OffsetReader {
+            inner: reader,
+            offset: 0,
+        };
+        match read_data(&mut f) {
+            Err(LoadError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(corrupt(f.offset, Corruption::UnexpectedEnd))
+            }
+            result => result,
+        }
+    }
+    pub fn write_to<W: Write>(&self, f: &mut W) -> io::Result<()> {
         f.write_all(MAGIC)?;
         f.write_u16::<LE>(VERSION)?;
         f.write_u32::<LE>(self.activities.len() as u32)?;
         for ac in &self.activities {
-            f.write_u8(ac.name.len() as u8)?;
-            f.write_all(ac.name.as_bytes())?;
-            f.write_u16::<LE>(ac.starting_date.year() as u16)?;
-            f.write_u8(ac.starting_date.month() as u8)?;
-            f.write_u8(ac.starting_date.day() as u8)?;
+            write_string(f, &ac.name)?;
+            write_date(f, ac.starting_date)?;
             f.write_u32::<LE>(ac.dates.len() as u32)?;
-            for date in &ac.dates {
-                f.write_u16::<LE>(date.year() as u16)?;
-                f.write_u8(date.month() as u8)?;
-                f.write_u8(date.day() as u8)?;
+            for &date in &ac.dates {
+                write_date(f, date)?;
+            }
+            f.write_u8(u8::from(ac.in_overview))?;
+            write_string(f, &ac.group)?;
+            f.write_u8(count_u8(ac.goals.len(), "goals")?)?;
+            for goal in &ac.goals {
+                let (kind, value) = match *goal {
+                    Goal::Streak(days) => (0, days),
+                    Goal::TotalDays(days) => (1, days),
+                    Goal::MonthlyCompletion(percent) => (2, u32::from(percent)),
+                };
+                f.write_u8(kind)?;
+                f.write_u32::<LE>(value)?;
             }
+            f.write_u8(ac.freezes.available)?;
+            f.write_u32::<LE>(ac.freezes.covered.len() as u32)?;
+            for &date in &ac.freezes.covered {
+                write_date(f, date)?;
+            }
+            f.write_u8(u8::from(ac.is_negative()))?;
+            f.write_u64::<LE>(ac.id.0)?;
+        }
+        f.write_u8(count_u8(self.achievements.earned.len(), "badges")?)?;
+        for (&badge, &date) in &self.achievements.earned {
+            f.write_u8(badge as u8)?;
+            write_date(f, date)?;
         }
         Ok(())
     }
 }
 
-fn save_path(data_dir: &Path) -> PathBuf {
-    data_dir.join("calen-do.dat")
+fn read_data<R: Read>(f: &mut OffsetReader<R>) -> Result<UserData, LoadError> {
+    let version = verify(f)?;
+    let offset = f.offset;
+    let n_activities = f.read_u32::<LE>()?;
+    if n_activities == 0 {
+        return Err(corrupt(offset, Corruption::NoActivities));
+    }
+    // Don't trust the count for allocating, it might be garbage
+    let mut activities = Vec::with_capacity(n_activities.min(256) as usize);
+    for _ in 0..n_activities {
+        let name = read_string(f)?;
+        let starting_date = read_date(f)?;
+        let mut set = BTreeSet::new();
+        for _ in 0..f.read_u32::<LE>()? {
+            set.insert(read_date(f)?);
+        }
+        // Version 1 had no overview filter
+        let in_overview = version < 2 || f.read_u8()? != 0;
+        let group = if version >= 3 {
+            read_string(f)?
+        } else {
+            String::new()
+        };
+        let mut goals = Vec::new();
+        if version >= 4 {
+            for _ in 0..f.read_u8()? {
+                let offset = f.offset;
+                let kind = f.read_u8()?;
+                let value = f.read_u32::<LE>()?;
+                goals.push(match kind {
+                    0 => Goal::Streak(value),
+                    1 => Goal::TotalDays(value),
+                    2 if value <= 100 => Goal::MonthlyCompletion(value as u8),
+                    2 => return Err(corrupt(offset, Corruption::InvalidPercent(value))),
+                    _ => return Err(corrupt(offset, Corruption::UnknownGoalKind(kind))),
+                });
+            }
+        }
+        let mut freezes = Freezes::default();
+        if version >= 6 {
+            let offset = f.offset;
+            freezes.available = f.read_u8()?;
+            if freezes.available > MAX_FREEZES {
+                return Err(corrupt(
+                    offset,
+                    Corruption::TooManyFreezes(freezes.available),
+                ));
+            }
+            for _ in 0..f.read_u32::<LE>()? {
+                freezes.covered.insert(read_date(f)?);
+            }
+        }
+        let polarity = if version >= 7 && f.read_u8()? != 0 {
+            Polarity::Negative
+        } else {
+            Polarity::Positive
+        };
+        // Activities from before IDs existed get new ones
+        let offset = f.offset;
+        let id = if version >= 8 {
+            ActivityId(f.read_u64::<LE>()?)
+        } else {
+            ActivityId::generate()
+        };
+        if activities.iter().any(|a: &Activity| a.id == id) {
+            return Err(corrupt(offset, Corruption::DuplicateId(id)));
+        }
+        activities.push(Activity {
+            id,
+            name,
+            starting_date,
+            dates: set,
+            in_overview,
+            group,
+            goals,
+            freezes,
+            polarity,
+        });
+    }
+    let mut achievements = Achievements::default();
+    if version >= 5 {
+        for _ in 0..f.read_u8()? {
+            let offset = f.offset;
+            let badge = f.read_u8()?;
+            let badge = *Badge::ALL
+                .get(badge as usize)
+                .ok_or_else(|| corrupt(offset, Corruption::UnknownBadge(badge)))?;
+            achievements.earned.insert(badge, read_date(f)?);
+        }
+    }
+    Ok(UserData {
+        activities,
+        achievements,
+    })
 }
 
 const MAGIC: &[u8] = b"CALDOSAVE";
-const VERSION: u16 = 1;
+const VERSION: u16 = 8;
 
-fn verify<R: Read>(reader: &mut R) -> Result<(), Box<dyn Error>> {
+/// Verifies the header, and returns the version of the save file
+fn verify<R: Read>(reader: &mut R) -> Result<u16, LoadError> {
     let mut buf = [0; MAGIC.len()];
     reader.read_exact(&mut buf)?;
     if buf != MAGIC {
-        return Err("Not a valid cal-do save file".into());
+        return Err(corrupt(0, Corruption::NotASaveFile));
     }
     let ver = reader.read_u16::<LE>()?;
-    if ver == VERSION {
-        Ok(())
+    if (1..=VERSION).contains(&ver) {
+        Ok(ver)
     } else {
-        Err(format!("Version mismatch: program ver: {VERSION} vs save ver: {ver}",).into())
+        Err(LoadError::VersionMismatch {
+            found: ver,
+            supported: VERSION,
+        })
+    }
+}
+
+#[cfg(test)]
+fn sample_save() -> Vec<u8> {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut data = UserData::new_default(date);
+    data.activities[0].dates.insert(date);
+    data.activities[0].goals.push(Goal::Streak(30));
+    data.achievements.earned.insert(Badge::Comeback, date);
+    let mut buf = Vec::new();
+    data.write_to(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_save_roundtrip() {
+    let data = UserData::read_from(&sample_save()[..]).unwrap();
+    let activity = &data.activities[0];
+    assert_eq!(activity.dates.len(), 1);
+    assert_eq!(activity.goals, [Goal::Streak(30)]);
+    assert_eq!(data.achievements.earned.len(), 1);
+    // Ids stay the same between loads
+    let id = activity.id;
+    let mut buf = Vec::new();
+    data.write_to(&mut buf).unwrap();
+    assert_eq!(UserData::read_from(&buf[..]).unwrap().activities[0].id, id);
+}
+
+#[test]
+fn test_load_missing() {
+    let path = Path::new("this-save-file-does-not-exist.dat");
+    assert!(matches!(UserData::load(path), Err(LoadError::Missing)));
+}
+
+#[cfg(test)]
+fn read_corrupt(buf: &[u8]) -> (u64, Corruption) {
+    match UserData::read_from(buf) {
+        Err(LoadError::Corrupt { offset, kind }) => (offset, kind),
+        Err(e) => panic!("Expected corrupt data, got {e}"),
+        Ok(_) => panic!("Expected corrupt data, but it loaded"),
+    }
+}
+
+#[test]
+fn test_load_corrupt() {
+    let mut buf = sample_save();
+    buf[0] = b'X';
+    assert_eq!(read_corrupt(&buf), (0, Corruption::NotASaveFile));
+    // Cut off in the middle of the first activity name
+    let buf = &sample_save()[..MAGIC.len() + 2 + 4 + 2];
+    assert_eq!(read_corrupt(buf), (17, Corruption::UnexpectedEnd));
+    // Unknown goal kind, right after the group name, which is empty
+    let mut buf = sample_save();
+    let name_len = usize::from(buf[MAGIC.len() + 2 + 4]);
+    let goal_kind = MAGIC.len() + 2 + 4 + 1 + name_len + 4 + 4 + 4 + 1 + 1 + 1;
+    buf[goal_kind] = 9;
+    assert_eq!(
+        read_corrupt(&buf),
+        (goal_kind as u64, Corruption::UnknownGoalKind(9))
+    );
+    // A monthly goal over 100%
+    let mut buf = sample_save();
+    buf[goal_kind] = 2;
+    buf[goal_kind + 1..goal_kind + 5].copy_from_slice(&101u32.to_le_bytes());
+    assert_eq!(
+        read_corrupt(&buf),
+        (goal_kind as u64, Corruption::InvalidPercent(101))
+    );
+}
+
+#[test]
+fn test_load_invalid_values() {
+    let activities = MAGIC.len() + 2;
+    let starting_date = activities + 4 + 1 + usize::from(sample_save()[activities + 4]);
+    let mut buf = sample_save();
+    buf[starting_date + 2] = 13;
+    assert_eq!(
+        read_corrupt(&buf),
+        (
+            starting_date as u64,
+            Corruption::InvalidDate {
+                year: 2023,
+                month: 13,
+                day: 31
+            }
+        )
+    );
+    let mut buf = sample_save();
+    buf[starting_date + 3] = 0;
+    assert!(matches!(
+        read_corrupt(&buf),
+        (_, Corruption::InvalidDate { day: 0, .. })
+    ));
+    let mut buf = sample_save();
+    buf[activities..activities + 4].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(
+        read_corrupt(&buf),
+        (activities as u64, Corruption::NoActivities)
+    );
+    // A huge activity count shouldn't allocate, only run out of data
+    let mut buf = sample_save();
+    buf[activities..activities + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(read_corrupt(&buf), (_, Corruption::UnexpectedEnd)));
+    let mut buf = sample_save();
+    buf[activities + 5] = 0xFF;
+    assert_eq!(
+        read_corrupt(&buf),
+        (activities as u64 + 4, Corruption::InvalidUtf8)
+    );
+}
+
+#[test]
+fn test_load_duplicate_id() {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut data = UserData::new_default(date);
+    data.activities.push(Activity::new_default(date));
+    data.activities[1].id = data.activities[0].id;
+    let mut buf = Vec::new();
+    data.write_to(&mut buf).unwrap();
+    let id_offset = buf.len() - 1 - 8;
+    assert_eq!(
+        read_corrupt(&buf),
+        (
+            id_offset as u64,
+            Corruption::DuplicateId(data.activities[0].id)
+        )
+    );
+}
+
+#[test]
+fn test_save_invalid_values() {
+    let date = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let mut data = UserData::new_default(date);
+    data.activities[0].name = "a".repeat(MAX_NAME_LEN + 1);
+    assert!(data.write_to(&mut Vec::new()).is_err());
+    data.activities[0].name.clear();
+    data.activities[0].goals = vec![Goal::Streak(1); 256];
+    assert!(data.write_to(&mut Vec::new()).is_err());
+}
+
+/// Small xorshift generator, so the fuzz tests are reproducible
+#[cfg(test)]
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[test]
+fn test_load_random_bytes_never_panics() {
+    let mut rng = 0x2545_F491_4F6C_DD1D;
+    for _ in 0..2000 {
+        let len = xorshift(&mut rng) % 128;
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&(xorshift(&mut rng) as u16 % (VERSION + 1)).to_le_bytes());
+        buf.extend((0..len).map(|_| xorshift(&mut rng) as u8));
+        let _ = UserData::read_from(&buf[..]);
+        let _ = UserData::read_from(&buf[MAGIC.len()..]);
+    }
+}
+
+#[test]
+fn test_load_mutated_save_never_panics() {
+    let save = sample_save();
+    let mut rng = 0x9E37_79B9_7F4A_7C15;
+    for _ in 0..5000 {
+        let mut buf = save.clone();
+        for _ in 0..1 + xorshift(&mut rng) % 4 {
+            let idx = xorshift(&mut rng) as usize % buf.len();
+            buf[idx] = xorshift(&mut rng) as u8;
+        }
+        let len = buf.len() - xorshift(&mut rng) as usize % 8;
+        buf.truncate(len);
+        let _ = UserData::read_from(&buf[..]);
+    }
+}
+
+#[test]
+fn test_load_version_mismatch() {
+    let mut buf = sample_save();
+    buf[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        UserData::read_from(&buf[..]),
+        Err(LoadError::VersionMismatch { found, supported: VERSION }) if found == VERSION + 1
+    ));
+}
+
+#[test]
+fn test_load_io_error() {
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::PermissionDenied.into())
+        }
     }
+    assert!(matches!(
+        UserData::read_from(Failing),
+        Err(LoadError::Io(e)) if e.kind() == io::ErrorKind::PermissionDenied
+    ));
 }